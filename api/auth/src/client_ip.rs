@@ -0,0 +1,140 @@
+//! Client Addresses
+//!
+//! Behind a reverse proxy every connection comes from the proxy, so
+//! the client's own address is taken from the `X-Forwarded-For` or
+//! `X-Real-IP` header the proxy sets. The headers are only believed
+//! on connections from configured trusted proxies, since any other
+//! client could set them to whatever it likes.
+
+use axum::{
+    extract::{connect_info::ConnectInfo, rejection::ExtensionRejection, FromRequestParts},
+    http::{request::Parts, HeaderMap, HeaderName},
+    RequestPartsExt,
+};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_REAL_IP: HeaderName = HeaderName::from_static("x-real-ip");
+
+/// Addresses of the reverse proxies whose forwarding headers are
+/// believed. Added to requests as an extension; without it, no
+/// proxy is trusted.
+#[derive(Clone, Default)]
+pub struct TrustedProxies(Arc<Vec<IpNet>>);
+
+impl TrustedProxies {
+    pub fn new(proxies: Vec<IpNet>) -> Self {
+        Self(Arc::new(proxies))
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|proxy| proxy.contains(&ip))
+    }
+
+    /// Resolve the address of the client behind a connection.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.contains(peer) {
+            return peer;
+        }
+
+        let hops: Vec<&str> = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect();
+
+        // Each proxy appends the address it was connected from, so
+        // the client is the nearest hop that isn't a trusted proxy.
+        // Hops further along were set by the client.
+        let mut forwarded = None;
+        for hop in hops.iter().rev() {
+            let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+                break;
+            };
+
+            forwarded = Some(ip);
+            if !self.contains(ip) {
+                break;
+            }
+        }
+
+        forwarded
+            .or_else(|| {
+                headers
+                    .get(X_REAL_IP)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse().ok())
+            })
+            .unwrap_or(peer)
+    }
+}
+
+/// Address of the client that sent a request, resolved through any
+/// trusted proxies. Requires the router to be served with
+/// `into_make_service_with_connect_info`.
+pub struct ClientIp(pub IpAddr);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = ExtensionRejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(addr) = parts.extract::<ConnectInfo<SocketAddr>>().await?;
+
+        let trusted_proxies = parts
+            .extensions
+            .get::<TrustedProxies>()
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(ClientIp(trusted_proxies.client_ip(addr.ip(), &parts.headers)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn proxies() -> TrustedProxies {
+        TrustedProxies::new(vec!["172.18.0.0/16".parse().unwrap()])
+    }
+
+    fn forwarded_for(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_FORWARDED_FOR, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_headers_are_ignored_from_untrusted_peers() {
+        let peer = "203.0.113.9".parse().unwrap();
+        let headers = forwarded_for("198.51.100.1");
+
+        assert_eq!(proxies().client_ip(peer, &headers), peer);
+        assert_eq!(TrustedProxies::default().client_ip(peer, &headers), peer);
+    }
+
+    #[test]
+    fn test_client_is_nearest_untrusted_hop() {
+        let peer = "172.18.0.2".parse().unwrap();
+
+        // The first hop was made up by the client
+        let headers = forwarded_for("10.0.0.1, 198.51.100.1, 172.18.0.3");
+        assert_eq!(proxies().client_ip(peer, &headers), "198.51.100.1".parse::<IpAddr>().unwrap());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(X_REAL_IP, HeaderValue::from_static("198.51.100.2"));
+        assert_eq!(proxies().client_ip(peer, &headers), "198.51.100.2".parse::<IpAddr>().unwrap());
+
+        assert_eq!(proxies().client_ip(peer, &HeaderMap::new()), peer);
+    }
+}
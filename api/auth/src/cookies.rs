@@ -0,0 +1,119 @@
+//! Cookie Authentication
+//!
+//! Browser clients can keep tokens out of reach of scripts by
+//! sending `X-Auth-Mode: cookie` when logging in. The access and
+//! refresh tokens are then set as `HttpOnly; Secure; SameSite=Strict`
+//! cookies rather than returned, along with a CSRF token cookie that
+//! scripts can read. Requests authenticated by cookie that change
+//! anything must echo the CSRF token in the `X-CSRF-Token` header.
+
+use axum::http::{HeaderMap, HeaderName, Method};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use cookie::time::Duration;
+use rand::Rng;
+use subtle::ConstantTimeEq;
+
+use errors::AuthError;
+
+use super::jwt::ACCESS_TOKEN_LIFETIME;
+
+/// Header with which clients ask for tokens in cookies
+pub const AUTH_MODE_HEADER: HeaderName = HeaderName::from_static("x-auth-mode");
+
+/// Header echoing the CSRF token cookie
+pub const CSRF_HEADER: HeaderName = HeaderName::from_static("x-csrf-token");
+
+pub const ACCESS_TOKEN_COOKIE: &str = "access_token";
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+pub const CSRF_TOKEN_COOKIE: &str = "csrf_token";
+
+/// Lifetime of the refresh token cookie, matching the expiry of
+/// refresh tokens
+const REFRESH_TOKEN_MAX_AGE: Duration = Duration::days(7);
+
+/// Whether a client asked for tokens in cookies.
+pub fn wants_cookies(headers: &HeaderMap) -> bool {
+    headers
+        .get(AUTH_MODE_HEADER)
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"cookie"))
+}
+
+fn cookie(name: &'static str, value: String, max_age: Duration, http_only: bool) -> Cookie<'static> {
+    Cookie::build((name, value))
+        .path("/")
+        .http_only(http_only)
+        .secure(true)
+        .same_site(SameSite::Strict)
+        .max_age(max_age)
+        .build()
+}
+
+/// Set the token cookies of a new or refreshed session, with a new
+/// CSRF token.
+pub fn set_session_cookies(jar: CookieJar, access_token: String, refresh_token: String) -> CookieJar {
+    let mut csrf_token = [0u8; 32];
+    rand::rng().fill(&mut csrf_token);
+
+    let access_max_age = Duration::seconds(ACCESS_TOKEN_LIFETIME.num_seconds());
+
+    let csrf_token = URL_SAFE_NO_PAD.encode(csrf_token);
+
+    jar.add(cookie(ACCESS_TOKEN_COOKIE, access_token, access_max_age, true))
+        .add(cookie(REFRESH_TOKEN_COOKIE, refresh_token, REFRESH_TOKEN_MAX_AGE, true))
+        .add(cookie(CSRF_TOKEN_COOKIE, csrf_token, REFRESH_TOKEN_MAX_AGE, false))
+}
+
+/// Remove the token cookies, e.g. on logout.
+pub fn clear_session_cookies(jar: CookieJar) -> CookieJar {
+    [ACCESS_TOKEN_COOKIE, REFRESH_TOKEN_COOKIE, CSRF_TOKEN_COOKIE]
+        .into_iter()
+        .fold(jar, |jar, name| jar.remove(Cookie::build(name).path("/")))
+}
+
+/// Check the CSRF header of a request authenticated by cookie
+/// against the CSRF token cookie. Requests that can't change
+/// anything need no CSRF token.
+pub fn check_csrf(method: &Method, headers: &HeaderMap, jar: &CookieJar) -> Result<(), AuthError> {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(());
+    }
+
+    let expected = jar
+        .get(CSRF_TOKEN_COOKIE)
+        .map(|cookie| cookie.value().as_bytes())
+        .ok_or(AuthError::InvalidCsrfToken)?;
+
+    let given = headers
+        .get(CSRF_HEADER)
+        .map(|value| value.as_bytes())
+        .ok_or(AuthError::InvalidCsrfToken)?;
+
+    if expected.is_empty() || !bool::from(expected.ct_eq(given)) {
+        return Err(AuthError::InvalidCsrfToken);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{header, HeaderValue};
+
+    #[test]
+    fn test_check_csrf_requires_matching_header_on_unsafe_methods() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_static("csrf_token=abc"));
+        let jar = CookieJar::from_headers(&headers);
+
+        assert!(check_csrf(&Method::GET, &headers, &jar).is_ok());
+        assert!(check_csrf(&Method::POST, &headers, &jar).is_err());
+
+        headers.insert(CSRF_HEADER, HeaderValue::from_static("abd"));
+        assert!(check_csrf(&Method::DELETE, &headers, &jar).is_err());
+
+        headers.insert(CSRF_HEADER, HeaderValue::from_static("abc"));
+        assert!(check_csrf(&Method::POST, &headers, &jar).is_ok());
+    }
+}
@@ -11,8 +11,8 @@ use headers::{
     Authorization,
 };
 use jsonwebtoken::{
-    errors::Error as JwtError,
-    Algorithm, Header, Validation,
+    errors::{Error as JwtError, ErrorKind},
+    Header, Validation,
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -30,17 +30,32 @@ pub struct Claims {
 
     /// Time JWT was issued
     pub iat: usize,
+
+    /// What the token may be used for, if not as an access token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
 }
 
+/// Scope of tokens that stand for a correct password awaiting a
+/// second factor
+const CHALLENGE_SCOPE: &str = "mfa_challenge";
+
+/// How long access tokens are valid for
+pub const ACCESS_TOKEN_LIFETIME: Duration = Duration::minutes(15);
+
 impl Claims {
     pub(crate) fn new(username: &str) -> Self {
+        Self::with_lifetime(username, ACCESS_TOKEN_LIFETIME)
+    }
+
+    fn with_lifetime(username: &str, lifetime: Duration) -> Self {
         let now = Utc::now();
-        let exp = (now + Duration::minutes(15)).timestamp() as usize;
 
         Self {
             sub: username.to_owned(),
-            exp,
+            exp: (now + lifetime).timestamp() as usize,
             iat: now.timestamp() as usize,
+            scope: None,
         }
     }
 }
@@ -66,24 +81,67 @@ impl<S> FromRequestParts<S> for Claims where S: Send + Sync {
     }
 }
 
-/// Generate a JWT access token.
+/// Generate a JWT access token, signed with the active key.
 pub fn create_access_token(username: &str) -> Result<String, JwtError> {
+    sign(&Claims::new(username))
+}
+
+/// Generate a short-lived token for a user who gave a correct
+/// password and still has to give a second factor.
+pub fn create_challenge_token(username: &str) -> Result<String, JwtError> {
+    let claims = Claims {
+        scope: Some(CHALLENGE_SCOPE.to_string()),
+        ..Claims::with_lifetime(username, Duration::minutes(5))
+    };
+
+    sign(&claims)
+}
+
+fn sign(claims: &Claims) -> Result<String, JwtError> {
     let keys = get_keys();
 
-    jsonwebtoken::encode(
-        &Header::default(),
-        &Claims::new(username),
-        &keys.encoding,
-    )
+    let mut header = Header::new(keys.algorithm);
+    header.kid = Some(keys.kid.clone());
+
+    jsonwebtoken::encode(&header, claims, &keys.encoding)
 }
 
+/// Validate a JWT access token against the key named in its header.
 pub(crate) fn validate_token(token: &str) -> Result<Claims, JwtError> {
+    let claims = decode(token)?;
+
+    // Tokens with a scope can't be used for access
+    if claims.scope.is_some() {
+        return Err(ErrorKind::InvalidToken.into());
+    }
+
+    Ok(claims)
+}
+
+/// Validate a second factor challenge token.
+pub fn validate_challenge_token(token: &str) -> Result<Claims, JwtError> {
+    let claims = decode(token)?;
+
+    if claims.scope.as_deref() != Some(CHALLENGE_SCOPE) {
+        return Err(ErrorKind::InvalidToken.into());
+    }
+
+    Ok(claims)
+}
+
+/// Decode a JWT, verifying it with the key named in its header.
+fn decode(token: &str) -> Result<Claims, JwtError> {
     let keys = get_keys();
 
+    let decoding_key = jsonwebtoken::decode_header(token)?
+        .kid
+        .and_then(|kid| keys.decoding.get(&kid))
+        .ok_or(ErrorKind::InvalidKeyFormat)?;
+
     jsonwebtoken::decode::<Claims>(
         token,
-        &keys.decoding,
-        &Validation::new(Algorithm::HS256),
+        decoding_key,
+        &Validation::new(keys.algorithm),
     )
     .map(|token_data| token_data.claims)
 }
@@ -1,5 +1,10 @@
-mod keys;
+pub mod client_ip;
+pub mod cookies;
 pub mod jwt;
+pub mod keys;
 pub mod middleware;
+pub mod throttle;
+pub mod totp;
+pub mod validation;
 
 pub use jwt::Claims;
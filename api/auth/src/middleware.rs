@@ -4,7 +4,7 @@ use axum::{
     http::request::Parts,
     RequestPartsExt,
 };
-use axum_extra::TypedHeader;
+use axum_extra::{extract::cookie::CookieJar, TypedHeader};
 use headers::{
     authorization::Bearer,
     Authorization,
@@ -14,9 +14,10 @@ use errors::AuthError;
 use models::UserInfo;
 use state::AppState;
 
-use super::jwt;
+use super::{cookies, jwt};
 
-/// Requires valid JWT for protected routes
+/// Requires valid JWT for protected routes, given either in the
+/// auth header or, along with a CSRF token, in a cookie
 pub struct RequireAuth(pub UserInfo);
 
 impl<S> FromRequestParts<S> for RequireAuth
@@ -32,14 +33,26 @@ where
     ) -> Result<Self, Self::Rejection> {
         let app_state = AppState::from_ref(state);
 
-        // Extract access token from auth header
-        let TypedHeader(Authorization(bearer)) = parts
-            .extract::<TypedHeader<Authorization<Bearer>>>()
-            .await
-            .map_err(|_| AuthError::BadOrMissingHeader)?;
+        // Extract access token from auth header, or failing that,
+        // from the access token cookie
+        let token = match parts.extract::<TypedHeader<Authorization<Bearer>>>().await {
+            Ok(TypedHeader(Authorization(bearer))) => bearer.token().to_string(),
+            Err(_) => {
+                let jar = CookieJar::from_headers(&parts.headers);
+                let token = jar
+                    .get(cookies::ACCESS_TOKEN_COOKIE)
+                    .ok_or(AuthError::BadOrMissingHeader)?
+                    .value()
+                    .to_string();
+
+                // Browsers send cookies with cross-site requests too
+                cookies::check_csrf(&parts.method, &parts.headers, &jar)?;
+                token
+            }
+        };
 
         // Extract claims from token data
-        let claims = jwt::validate_token(bearer.token())
+        let claims = jwt::validate_token(&token)
             .map_err(|_| AuthError::InvalidToken)?;
 
         // Retrieve user info using claims subject
@@ -0,0 +1,204 @@
+//! Login Throttling
+//!
+//! Failed logins are counted per username and per client IP. Past
+//! a number of free attempts, each further failure locks the key
+//! out for twice as long as the last, and every lockout is written
+//! to the audit log. Counts start over after an hour without
+//! failures, or for a username, after a successful login.
+
+use chrono::{TimeDelta, Utc};
+use std::net::{IpAddr, Ipv6Addr};
+use tracing::warn;
+
+use errors::AuthError;
+use models::{AuditEvent, LoginAttempts};
+use state::AppState;
+
+/// How long after the last failure the count starts over
+const RESET_AFTER: TimeDelta = TimeDelta::hours(1);
+
+/// Backoff applied to one kind of key.
+pub struct ThrottlePolicy {
+    /// Failures allowed before the first lockout
+    pub free_attempts: i32,
+
+    /// Length of the first lockout
+    pub base_delay: TimeDelta,
+
+    /// Longest lockout
+    pub max_delay: TimeDelta,
+}
+
+/// Failed logins against one username, whichever client they're from
+pub const USERNAME_POLICY: ThrottlePolicy = ThrottlePolicy {
+    free_attempts: 5,
+    base_delay: TimeDelta::seconds(15),
+    max_delay: TimeDelta::minutes(15),
+};
+
+/// Failed logins from one client, against any username. Clients are
+/// allowed more attempts, since they may be shared by many users.
+pub const CLIENT_POLICY: ThrottlePolicy = ThrottlePolicy {
+    free_attempts: 20,
+    base_delay: TimeDelta::seconds(15),
+    max_delay: TimeDelta::minutes(15),
+};
+
+impl ThrottlePolicy {
+    /// How long to lock a key out for after a number of failures.
+    pub fn lockout(&self, failures: i32) -> Option<TimeDelta> {
+        let excess = failures - self.free_attempts;
+        if excess <= 0 {
+            return None;
+        }
+
+        // Capped so the factor stays a positive i32
+        let factor = 1_i32 << (excess - 1).min(30);
+        let delay = self.base_delay.checked_mul(factor).unwrap_or(self.max_delay);
+        Some(delay.min(self.max_delay))
+    }
+}
+
+fn username_key(username: &str) -> String {
+    format!("user:{}", username.to_lowercase())
+}
+
+/// IPv6 clients usually control a whole /64, so they're counted
+/// by network.
+fn client_key(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => format!("ip:{}", ip),
+        IpAddr::V6(ip) => {
+            let network = u128::from(ip) & !((1_u128 << 64) - 1);
+            format!("ip:{}/64", Ipv6Addr::from(network))
+        }
+    }
+}
+
+/// Seconds to tell a client to wait, rounded up.
+fn retry_after(remaining: TimeDelta) -> u64 {
+    let millis = remaining.num_milliseconds().max(0) as u64;
+    millis.div_ceil(1000).max(1)
+}
+
+/// Reject a login attempt if the client or username is locked out.
+pub async fn check(state: &AppState, username: &str, ip: IpAddr) -> Result<(), AuthError> {
+    let repo = &state.login_attempt_repo;
+
+    let client = repo
+        .find(&client_key(ip))
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    if let Some(remaining) = client.as_ref().and_then(LoginAttempts::locked_for) {
+        return Err(AuthError::TooManyAttempts(retry_after(remaining)));
+    }
+
+    let user = repo
+        .find(&username_key(username))
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    if let Some(remaining) = user.as_ref().and_then(LoginAttempts::locked_for) {
+        return Err(AuthError::AccountLocked(retry_after(remaining)));
+    }
+
+    Ok(())
+}
+
+/// Count a failed login against the client and username, locking
+/// either out if it has failed too often.
+pub async fn record_failure(state: &AppState, username: &str, ip: IpAddr) -> Result<(), AuthError> {
+    let reset_before = Utc::now() - RESET_AFTER;
+
+    // A client's lockout isn't tied to whichever username its last
+    // attempt was against, or anyone could fill others' audit logs
+    for (key, policy, event_username) in [
+        (client_key(ip), &CLIENT_POLICY, None),
+        (username_key(username), &USERNAME_POLICY, Some(username)),
+    ] {
+        let attempts = state
+            .login_attempt_repo
+            .record_failure(&key, reset_before)
+            .await
+            .map_err(|_| AuthError::QueryFailure)?;
+
+        let Some(lockout) = policy.lockout(attempts.failures) else {
+            continue;
+        };
+
+        state
+            .login_attempt_repo
+            .lock(&key, Utc::now() + lockout)
+            .await
+            .map_err(|_| AuthError::QueryFailure)?;
+
+        let detail = format!(
+            "{} locked out for {}s after {} failed logins",
+            key,
+            lockout.num_seconds(),
+            attempts.failures,
+        );
+        warn!("Login lockout: {}", detail);
+
+        let event = AuditEvent::new("login_lockout", event_username, Some(ip), detail);
+        if let Err(e) = state.audit_log_repo.record(&event).await {
+            warn!("Failed to write audit log: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Forget a username's failed logins after it logs in successfully.
+/// The client's count is kept, so that logging in to an account of
+/// one's own doesn't allow guessing others' passwords for longer.
+pub async fn record_success(state: &AppState, username: &str) -> Result<(), AuthError> {
+    state
+        .login_attempt_repo
+        .clear(&username_key(username))
+        .await
+        .map_err(|_| AuthError::QueryFailure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_doubles_up_to_max() {
+        let lockouts: Vec<_> = (4..=14)
+            .map(|failures| USERNAME_POLICY.lockout(failures).map(|d| d.num_seconds()))
+            .collect();
+
+        assert_eq!(
+            lockouts,
+            [
+                None,
+                None,
+                Some(15),
+                Some(30),
+                Some(60),
+                Some(120),
+                Some(240),
+                Some(480),
+                Some(900),
+                Some(900),
+                Some(900),
+            ],
+        );
+        for failures in [37, 1000, i32::MAX] {
+            assert_eq!(USERNAME_POLICY.lockout(failures).map(|d| d.num_seconds()), Some(900));
+        }
+    }
+
+    #[test]
+    fn test_ipv6_clients_are_counted_by_network() {
+        let a: IpAddr = "2001:db8:1:2:aaaa::1".parse().unwrap();
+        let b: IpAddr = "2001:db8:1:2:bbbb::2".parse().unwrap();
+        let c: IpAddr = "2001:db8:1:3::1".parse().unwrap();
+
+        assert_eq!(client_key(a), client_key(b));
+        assert_ne!(client_key(a), client_key(c));
+    }
+}
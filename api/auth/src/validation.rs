@@ -0,0 +1,211 @@
+//! User Input Validation
+//!
+//! Usernames must be 3 to 32 characters of ASCII letters, digits,
+//! `_`, `-` or `.`, starting with a letter or digit, and can't be
+//! one of a few reserved names. Passwords must follow the
+//! configured [`PasswordPolicy`].
+
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::OnceCell;
+use std::collections::HashSet;
+use std::fs;
+
+use config::PasswordPolicyConfig;
+use errors::{AuthError, FieldError};
+use models::User;
+
+const USERNAME_MIN_LENGTH: usize = 3;
+const USERNAME_MAX_LENGTH: usize = 32;
+
+/// Names that could be mistaken for the service itself, compared
+/// case-insensitively
+const RESERVED_USERNAMES: [&str; 12] = [
+    "admin",
+    "administrator",
+    "api",
+    "imgmesser",
+    "me",
+    "null",
+    "root",
+    "support",
+    "system",
+    "undefined",
+    "user",
+    "users",
+];
+
+/// Rules new passwords must follow.
+pub struct PasswordPolicy {
+    min_length: usize,
+
+    /// Known breached passwords
+    breached: HashSet<String>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self::new(PasswordPolicyConfig::default().min_length, HashSet::new())
+    }
+}
+
+impl PasswordPolicy {
+    pub fn new(min_length: usize, breached: HashSet<String>) -> Self {
+        Self { min_length, breached }
+    }
+
+    /// Make the policy, reading the breached password list if one
+    /// is configured.
+    pub fn from_config(config: &PasswordPolicyConfig) -> Result<Self> {
+        let breached = match config.breached_passwords_file {
+            Some(ref path) => {
+                let list = fs::read_to_string(path).with_context(|| {
+                    format!("Failed to read breached password file {}", path.display())
+                })?;
+
+                list.lines()
+                    .filter(|line| !line.is_empty())
+                    .map(String::from)
+                    .collect()
+            }
+            None => HashSet::new(),
+        };
+
+        Ok(Self::new(config.min_length, breached))
+    }
+
+    /// Check a new password against the policy.
+    pub fn check(&self, password: &str) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if password.chars().count() < self.min_length {
+            errors.push(FieldError::new(
+                "password",
+                "too_short",
+                format!("Password must be at least {} characters", self.min_length),
+            ));
+        }
+
+        if self.breached.contains(password) {
+            errors.push(FieldError::new(
+                "password",
+                "breached",
+                "Password has appeared in a data breach",
+            ));
+        }
+
+        errors
+    }
+}
+
+static POLICY: OnceCell<PasswordPolicy> = OnceCell::new();
+
+/// Install the password policy. Without it, the default policy
+/// with no breached password list is used.
+pub fn init(policy: PasswordPolicy) -> Result<()> {
+    POLICY
+        .set(policy)
+        .map_err(|_| anyhow!("Password policy is already initialized"))
+}
+
+/// Load the configured password policy and install it.
+pub async fn init_from_config() -> Result<()> {
+    let config = config::get_password_policy_config().await?;
+    init(PasswordPolicy::from_config(&config)?)
+}
+
+pub fn get_password_policy() -> &'static PasswordPolicy {
+    POLICY.get_or_init(PasswordPolicy::default)
+}
+
+/// Check a username against the naming rules.
+pub fn check_username(username: &str) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    let length = username.chars().count();
+    if !(USERNAME_MIN_LENGTH..=USERNAME_MAX_LENGTH).contains(&length) {
+        errors.push(FieldError::new(
+            "username",
+            "invalid_length",
+            format!(
+                "Username must be {} to {} characters",
+                USERNAME_MIN_LENGTH, USERNAME_MAX_LENGTH,
+            ),
+        ));
+    }
+
+    let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+    let starts_well = username.starts_with(|c: char| c.is_ascii_alphanumeric());
+    if !username.chars().all(allowed) || !starts_well {
+        errors.push(FieldError::new(
+            "username",
+            "invalid_characters",
+            "Username may only contain letters, digits, '_', '-' and '.', \
+            and must start with a letter or digit",
+        ));
+    }
+
+    if RESERVED_USERNAMES.iter().any(|name| name.eq_ignore_ascii_case(username)) {
+        errors.push(FieldError::new(
+            "username",
+            "reserved",
+            "Username is reserved",
+        ));
+    }
+
+    errors
+}
+
+/// Check the credentials of a new user.
+pub fn validate_new_user(user: &User) -> Result<(), AuthError> {
+    if user.username.is_empty() || user.password.is_empty() {
+        return Err(AuthError::MissingCredentials);
+    }
+
+    let mut errors = check_username(&user.username);
+    errors.extend(get_password_policy().check(&user.password));
+
+    if !errors.is_empty() {
+        return Err(AuthError::InvalidUserInput(errors));
+    }
+
+    Ok(())
+}
+
+/// Check a user's new password against the password policy.
+pub fn validate_new_password(password: &str) -> Result<(), AuthError> {
+    let errors = get_password_policy().check(password);
+
+    if !errors.is_empty() {
+        return Err(AuthError::InvalidUserInput(errors));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(errors: Vec<FieldError>) -> Vec<&'static str> {
+        errors.into_iter().map(|e| e.code).collect()
+    }
+
+    #[test]
+    fn test_check_username() {
+        assert!(check_username("alice_01").is_empty());
+        assert!(check_username("a.b-c").is_empty());
+        assert_eq!(codes(check_username("al")), ["invalid_length"]);
+        assert_eq!(codes(check_username("al ice")), ["invalid_characters"]);
+        assert_eq!(codes(check_username(".alice")), ["invalid_characters"]);
+        assert_eq!(codes(check_username("Admin")), ["reserved"]);
+    }
+
+    #[test]
+    fn test_password_policy() {
+        let policy = PasswordPolicy::new(10, HashSet::from(["password1234".to_string()]));
+
+        assert!(policy.check("correct horse").is_empty());
+        assert_eq!(codes(policy.check("hunter22")), ["too_short"]);
+        assert_eq!(codes(policy.check("password1234")), ["breached"]);
+    }
+}
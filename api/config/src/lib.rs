@@ -3,21 +3,32 @@ use aws_config::{
     meta::region::RegionProviderChain,
     BehaviorVersion,
 };
-use aws_sdk_ssm::Client;
+use aws_sdk_ssm::{
+    error::SdkError,
+    operation::get_parameter::GetParameterError,
+    Client,
+};
 use axum::http::HeaderValue;
-use dotenv;
+use ipnet::IpNet;
 use std::env;
-use std::net::SocketAddr;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::process::Command;
 use std::str;
+use std::time::Duration;
 
-pub struct Addresses {
+/// Server listening address, accepted origin, the path prefix
+/// routes are served under, if any, and the reverse proxies whose
+/// forwarded client addresses are believed.
+pub struct ServerConfig {
     pub listener: SocketAddr,
     pub origin: HeaderValue,
+    pub path_prefix: Option<String>,
+    pub trusted_proxies: Vec<IpNet>,
 }
 
-impl Addresses {
+impl ServerConfig {
     pub fn new(origin_address: &str, listener_port: &str, is_prod: bool) -> Result<Self> {
         let origin = origin_address
             .parse::<HeaderValue>()
@@ -33,7 +44,37 @@ impl Addresses {
             SocketAddr::from(([127, 0, 0, 1], port))
         };
 
-        Ok(Self { listener, origin })
+        Ok(Self {
+            listener,
+            origin,
+            path_prefix: None,
+            trusted_proxies: Vec::new(),
+        })
+    }
+
+    /// Serve routes under the given path prefix, e.g. `/api`.
+    pub fn with_path_prefix(mut self, path_prefix: &str) -> Self {
+        let path_prefix = path_prefix.trim_end_matches('/');
+        self.path_prefix = (!path_prefix.is_empty())
+            .then(|| format!("/{}", path_prefix.trim_start_matches('/')));
+        self
+    }
+
+    /// Trust the forwarding headers of requests from the given
+    /// comma-separated addresses or networks, e.g. `172.18.0.0/16`.
+    pub fn with_trusted_proxies(mut self, trusted_proxies: &str) -> Result<Self> {
+        self.trusted_proxies = trusted_proxies
+            .split(',')
+            .map(str::trim)
+            .filter(|proxy| !proxy.is_empty())
+            .map(|proxy| {
+                proxy
+                    .parse::<IpNet>()
+                    .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+                    .with_context(|| format!("Failed to parse trusted proxy {}", proxy))
+            })
+            .collect::<Result<_>>()?;
+        Ok(self)
     }
 }
 
@@ -55,6 +96,164 @@ impl DbConfig {
     }
 }
 
+/// Where image objects are stored.
+pub enum StorageConfig {
+    /// A versioned AWS S3 bucket
+    S3,
+
+    /// A directory on the local filesystem
+    Local { root: PathBuf },
+}
+
+impl StorageConfig {
+    pub fn new(backend: &str, local_path: Option<String>) -> Result<Self> {
+        match backend {
+            "s3" => Ok(StorageConfig::S3),
+            "local" => {
+                let root = local_path
+                    .context("Missing local storage path for local object store")?;
+                Ok(StorageConfig::Local { root: PathBuf::from(root) })
+            }
+            _ => bail!("Unknown object store: {}", backend),
+        }
+    }
+}
+
+/// Argon2id cost parameters for password hashing. Defaults follow
+/// the OWASP recommendation of 19 MiB of memory and 2 iterations.
+pub struct PasswordHashConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for PasswordHashConfig {
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl PasswordHashConfig {
+    pub fn new(
+        memory_kib: Option<String>,
+        iterations: Option<String>,
+        parallelism: Option<String>,
+    ) -> Result<Self> {
+        let default = Self::default();
+
+        let parse = |value: Option<String>, default: u32, name: &str| -> Result<u32> {
+            value.map_or(Ok(default), |v| {
+                v.parse::<u32>()
+                    .with_context(|| format!("Failed to parse {} as u32", name))
+            })
+        };
+
+        Ok(Self {
+            memory_kib: parse(memory_kib, default.memory_kib, "Argon2 memory cost")?,
+            iterations: parse(iterations, default.iterations, "Argon2 iterations")?,
+            parallelism: parse(parallelism, default.parallelism, "Argon2 parallelism")?,
+        })
+    }
+}
+
+/// Rules new passwords must follow.
+pub struct PasswordPolicyConfig {
+    pub min_length: usize,
+
+    /// File of known breached passwords, one per line
+    pub breached_passwords_file: Option<PathBuf>,
+}
+
+impl Default for PasswordPolicyConfig {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            breached_passwords_file: None,
+        }
+    }
+}
+
+impl PasswordPolicyConfig {
+    pub fn new(min_length: Option<String>, breached_passwords_file: Option<String>) -> Result<Self> {
+        let min_length = match min_length {
+            Some(value) => value
+                .parse::<usize>()
+                .context("Failed to parse minimum password length")?,
+            None => Self::default().min_length,
+        };
+
+        Ok(Self {
+            min_length,
+            breached_passwords_file: breached_passwords_file.map(PathBuf::from),
+        })
+    }
+}
+
+/// Schedule of the background purge of refresh tokens.
+pub struct TokenPurgeConfig {
+    /// Time between purges
+    pub interval: Duration,
+
+    /// How long used tokens are kept after use, so that their reuse
+    /// is still detected
+    pub retention: Duration,
+
+    /// Most tokens deleted per query
+    pub batch_size: i64,
+}
+
+impl Default for TokenPurgeConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60 * 60),
+            retention: Duration::from_secs(7 * 24 * 60 * 60),
+            batch_size: 1000,
+        }
+    }
+}
+
+impl TokenPurgeConfig {
+    pub fn new(
+        interval_secs: Option<String>,
+        retention_secs: Option<String>,
+        batch_size: Option<String>,
+    ) -> Result<Self> {
+        let default = Self::default();
+
+        let parse_secs = |value: Option<String>, default: Duration, name: &str| -> Result<Duration> {
+            value.map_or(Ok(default), |v| {
+                v.parse::<u64>()
+                    .map(Duration::from_secs)
+                    .with_context(|| format!("Failed to parse {} as seconds", name))
+            })
+        };
+
+        let interval = parse_secs(interval_secs, default.interval, "token purge interval")?;
+        if interval.is_zero() {
+            bail!("Token purge interval must be positive");
+        }
+
+        let batch_size = match batch_size {
+            Some(value) => value
+                .parse::<i64>()
+                .ok()
+                .filter(|size| *size > 0)
+                .context("Failed to parse token purge batch size as a positive integer")?,
+            None => default.batch_size,
+        };
+
+        Ok(Self {
+            interval,
+            retention: parse_secs(retention_secs, default.retention, "token retention")?,
+            batch_size,
+        })
+    }
+}
+
 /// Make a new AWS SSM client.
 pub async fn get_ssm_client() -> Result<Client> {
     let region_provider = RegionProviderChain::default_provider()
@@ -85,8 +284,30 @@ pub async fn get_ssm_param(client: &Client, name: &str, decrypt: bool) -> Result
     Ok(None)
 }
 
-/// Get the server's listening IP address and accepted origin IP address.
-pub async fn get_addresses() -> Result<Addresses> {
+/// Get the value of an SSM parameter that may not exist. Failing to
+/// get one that does, e.g. due to throttling or denied access, is
+/// still an error, so that a setting isn't quietly dropped.
+pub async fn get_optional_ssm_param(
+    client: &Client,
+    name: &str,
+    decrypt: bool,
+) -> Result<Option<String>> {
+    match get_ssm_param(client, name, decrypt).await {
+        Err(e) if is_parameter_not_found(&e) => Ok(None),
+        result => result,
+    }
+}
+
+fn is_parameter_not_found(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<SdkError<GetParameterError>>()
+        .and_then(SdkError::as_service_error)
+        .is_some_and(GetParameterError::is_parameter_not_found)
+}
+
+/// Get the server's listening IP address, accepted origin IP address,
+/// optional path prefix, and trusted proxies.
+pub async fn get_server_config() -> Result<ServerConfig> {
     match get_env().as_str() {
         "prod" => {
             let ssm_client = get_ssm_client().await?;
@@ -99,8 +320,18 @@ pub async fn get_addresses() -> Result<Addresses> {
                 .await?
                 .ok_or(anyhow!("Empty SSM parameter"))?;
 
-            let addresses = Addresses::new(&origin_address, &listener_port, true)?;
-            Ok(addresses)
+            let path_prefix = get_optional_ssm_param(&ssm_client, "path-prefix", false)
+                .await?
+                .unwrap_or_default();
+
+            let trusted_proxies = get_optional_ssm_param(&ssm_client, "trusted-proxies", false)
+                .await?
+                .unwrap_or_default();
+
+            let config = ServerConfig::new(&origin_address, &listener_port, true)?
+                .with_path_prefix(&path_prefix)
+                .with_trusted_proxies(&trusted_proxies)?;
+            Ok(config)
         }
         _ => {
             load_env()?;
@@ -111,8 +342,13 @@ pub async fn get_addresses() -> Result<Addresses> {
             let listener_port = env::var("LISTENER_PORT")
                 .context("Missing env variable: LISTENER_PORT")?;
 
-            let addresses = Addresses::new(&origin_address, &listener_port, false)?;
-            Ok(addresses)
+            let path_prefix = env::var("PATH_PREFIX").unwrap_or_default();
+            let trusted_proxies = env::var("TRUSTED_PROXIES").unwrap_or_default();
+
+            let config = ServerConfig::new(&origin_address, &listener_port, false)?
+                .with_path_prefix(&path_prefix)
+                .with_trusted_proxies(&trusted_proxies)?;
+            Ok(config)
         }
     }
 }
@@ -168,6 +404,144 @@ pub async fn get_s3_bucket_name() -> Result<String> {
     }
 }
 
+/// Get the configured object store, which defaults to S3.
+pub async fn get_storage_config() -> Result<StorageConfig> {
+    match get_env().as_str() {
+        "prod" => {
+            let ssm_client = get_ssm_client().await?;
+
+            let backend = get_optional_ssm_param(&ssm_client, "object-store", false)
+                .await?
+                .unwrap_or_else(|| "s3".to_string());
+
+            let local_path = get_optional_ssm_param(&ssm_client, "local-storage-path", false)
+                .await?;
+
+            StorageConfig::new(&backend, local_path)
+        }
+        _ => {
+            load_env()?;
+
+            let backend = env::var("OBJECT_STORE")
+                .unwrap_or_else(|_| "s3".to_string());
+            let local_path = env::var("LOCAL_STORAGE_PATH").ok();
+
+            StorageConfig::new(&backend, local_path)
+        }
+    }
+}
+
+/// Get the JWT key set document, read from the file named by
+/// `JWT_KEY_FILE` if it's set, or else from config. Outside of
+/// production, no key set may be configured.
+pub async fn get_jwt_key_set() -> Result<Option<String>> {
+    if get_env() != "prod" {
+        load_env()?;
+    }
+
+    if let Ok(path) = env::var("JWT_KEY_FILE") {
+        let document = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read JWT key file {}", path))?;
+        return Ok(Some(document));
+    }
+
+    match get_env().as_str() {
+        "prod" => {
+            let ssm_client = get_ssm_client().await?;
+            let document = get_ssm_param(&ssm_client, "jwt-keys", true)
+                .await?
+                .ok_or(anyhow!("Empty SSM parameter"))?;
+            Ok(Some(document))
+        }
+        _ => Ok(env::var("JWT_KEYS").ok()),
+    }
+}
+
+/// Get the configured password hashing cost, falling back to
+/// defaults for any parameter that isn't set.
+pub async fn get_password_hash_config() -> Result<PasswordHashConfig> {
+    match get_env().as_str() {
+        "prod" => {
+            let ssm_client = get_ssm_client().await?;
+
+            let memory_kib = get_optional_ssm_param(&ssm_client, "argon2-memory-kib", false)
+                .await?;
+
+            let iterations = get_optional_ssm_param(&ssm_client, "argon2-iterations", false)
+                .await?;
+
+            let parallelism = get_optional_ssm_param(&ssm_client, "argon2-parallelism", false)
+                .await?;
+
+            PasswordHashConfig::new(memory_kib, iterations, parallelism)
+        }
+        _ => {
+            load_env()?;
+
+            PasswordHashConfig::new(
+                env::var("ARGON2_MEMORY_KIB").ok(),
+                env::var("ARGON2_ITERATIONS").ok(),
+                env::var("ARGON2_PARALLELISM").ok(),
+            )
+        }
+    }
+}
+
+/// Get the configured password policy.
+pub async fn get_password_policy_config() -> Result<PasswordPolicyConfig> {
+    match get_env().as_str() {
+        "prod" => {
+            let ssm_client = get_ssm_client().await?;
+
+            let min_length = get_optional_ssm_param(&ssm_client, "password-min-length", false)
+                .await?;
+
+            let breached_passwords_file = get_optional_ssm_param(&ssm_client, "breached-passwords-file", false)
+                .await?;
+
+            PasswordPolicyConfig::new(min_length, breached_passwords_file)
+        }
+        _ => {
+            load_env()?;
+
+            PasswordPolicyConfig::new(
+                env::var("PASSWORD_MIN_LENGTH").ok(),
+                env::var("BREACHED_PASSWORDS_FILE").ok(),
+            )
+        }
+    }
+}
+
+/// Get the configured refresh token purge schedule, falling back
+/// to defaults for any setting that isn't set.
+pub async fn get_token_purge_config() -> Result<TokenPurgeConfig> {
+    match get_env().as_str() {
+        "prod" => {
+            let ssm_client = get_ssm_client().await?;
+
+            let interval = get_optional_ssm_param(&ssm_client, "token-purge-interval-secs", false)
+                .await?;
+
+            let retention = get_optional_ssm_param(&ssm_client, "token-retention-secs", false)
+                .await?;
+
+            let batch_size = get_optional_ssm_param(&ssm_client, "token-purge-batch-size", false)
+                .await?;
+
+            TokenPurgeConfig::new(interval, retention, batch_size)
+        }
+        _ => {
+            load_env()?;
+
+            TokenPurgeConfig::new(
+                env::var("TOKEN_PURGE_INTERVAL_SECS").ok(),
+                env::var("TOKEN_RETENTION_SECS").ok(),
+                env::var("TOKEN_PURGE_BATCH_SIZE").ok(),
+            )
+        }
+    }
+}
+
 /// Load environment variables.
 fn load_env() -> Result<()> {
     // Locate workspace root
@@ -1,3 +1,7 @@
+DROP TABLE recovery_codes;
+DROP TABLE totp_credentials;
+DROP TABLE audit_log;
+DROP TABLE login_attempts;
 DROP TABLE refresh_tokens;
 DROP TABLE image_version;
 DROP TABLE image;
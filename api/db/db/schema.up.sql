@@ -1,12 +1,45 @@
+-- Usernames must be unique regardless of case before the index
+-- enforcing it can be made, so stop before changing anything if an
+-- existing database has some that differ only in case. Rename all
+-- but one of each (see the README) and run this again.
+DO $$
+DECLARE
+    duplicates text;
+BEGIN
+    IF to_regclass('user_profile') IS NOT NULL
+        AND to_regclass('idx_user_profile_username_lower') IS NULL
+    THEN
+        SELECT string_agg(names, '; ') INTO duplicates
+        FROM (
+            SELECT string_agg(username, ', ' ORDER BY username) AS names
+            FROM user_profile
+            GROUP BY lower(username)
+            HAVING COUNT(*) > 1
+        ) AS same_name;
+
+        IF duplicates IS NOT NULL THEN
+            RAISE EXCEPTION 'Usernames differ only in case: %', duplicates;
+        END IF;
+    END IF;
+END
+$$;
+
 CREATE EXTENSION IF NOT EXISTS "pgcrypto";
 CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
 
 CREATE TABLE IF NOT EXISTS user_profile (
     username text PRIMARY KEY,
-    password varchar(100) NOT NULL,
+    password text NOT NULL,
     object_base_path text NOT NULL
 );
 
+-- Usernames are unique regardless of case
+CREATE UNIQUE INDEX IF NOT EXISTS idx_user_profile_username_lower
+    ON user_profile(lower(username));
+
+-- Argon2id hashes don't fit the column made for MD5-crypt hashes
+ALTER TABLE user_profile ALTER COLUMN password TYPE text;
+
 CREATE TABLE IF NOT EXISTS image (
     id uuid PRIMARY KEY,
     name text,
@@ -14,9 +47,14 @@ CREATE TABLE IF NOT EXISTS image (
     username text NOT NULL REFERENCES user_profile(username)
         ON DELETE CASCADE,
     created_at timestamptz NOT NULL DEFAULT NOW(),
+    object_key text,
     CONSTRAINT uniq_name_username UNIQUE(name, username)
 );
 
+-- Images from before object keys were stored keep the path derived
+-- from their id and name while the key is NULL
+ALTER TABLE image ADD COLUMN IF NOT EXISTS object_key text;
+
 CREATE TABLE IF NOT EXISTS image_version (
     image_id uuid REFERENCES image(id) ON DELETE CASCADE,
     version text,
@@ -25,26 +63,113 @@ CREATE TABLE IF NOT EXISTS image_version (
     width int NOT NULL,
     height int NOT NULL,
     size bigint NOT NULL DEFAULT 0,
+    content_type int,
+    operations jsonb,
     PRIMARY KEY(image_id, version)
 );
 
+-- Versions from before format conversion take the image's type
+ALTER TABLE image_version ADD COLUMN IF NOT EXISTS content_type int;
+ALTER TABLE image_version ADD COLUMN IF NOT EXISTS operations jsonb;
+
 CREATE TABLE IF NOT EXISTS refresh_tokens (
     id uuid PRIMARY KEY DEFAULT uuid_generate_v4(),
     username text NOT NULL REFERENCES user_profile(username)
         ON DELETE CASCADE,
-    token varchar(255) UNIQUE NOT NULL,
+    token_hash text NOT NULL,
     expires_at timestamptz NOT NULL DEFAULT (NOW() + INTERVAL '7 days'),
     is_used boolean NOT NULL DEFAULT FALSE,
     used_at timestamptz,
     created_at timestamptz DEFAULT NOW(),
-    last_used_at timestamptz DEFAULT NOW()
+    last_used_at timestamptz DEFAULT NOW(),
+    family_id uuid NOT NULL,
+    user_agent text,
+    client_ip text,
+    session_created_at timestamptz NOT NULL DEFAULT NOW()
 );
 
-CREATE INDEX IF NOT EXISTS idx_refresh_tokens_token
-    ON refresh_tokens(token);
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS user_agent text;
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS client_ip text;
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS family_id uuid;
+-- Existing tokens each start their own family
+UPDATE refresh_tokens SET family_id = id WHERE family_id IS NULL;
+ALTER TABLE refresh_tokens ALTER COLUMN family_id SET NOT NULL;
+
+-- Existing sessions start when their oldest remaining token was made
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS session_created_at timestamptz;
+UPDATE refresh_tokens SET session_created_at = family.created_at
+    FROM (
+        SELECT family_id, COALESCE(MIN(created_at), NOW()) AS created_at
+        FROM refresh_tokens GROUP BY family_id
+    ) AS family
+    WHERE refresh_tokens.family_id = family.family_id
+        AND refresh_tokens.session_created_at IS NULL;
+ALTER TABLE refresh_tokens ALTER COLUMN session_created_at SET DEFAULT NOW();
+ALTER TABLE refresh_tokens ALTER COLUMN session_created_at SET NOT NULL;
+
+-- Replace plaintext tokens with their SHA-256 hashes
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_name = 'refresh_tokens' AND column_name = 'token'
+    ) THEN
+        ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS token_hash text;
+        UPDATE refresh_tokens
+            SET token_hash = encode(sha256(convert_to(token, 'UTF8')), 'hex');
+        ALTER TABLE refresh_tokens ALTER COLUMN token_hash SET NOT NULL;
+        ALTER TABLE refresh_tokens DROP COLUMN token;
+    END IF;
+END
+$$;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_refresh_tokens_token_hash
+    ON refresh_tokens(token_hash);
 CREATE INDEX IF NOT EXISTS idx_refresh_tokens_username
     ON refresh_tokens(username);
 CREATE INDEX IF NOT EXISTS idx_refresh_tokens_expires_at
     ON refresh_tokens(expires_at);
 CREATE INDEX IF NOT EXISTS idx_refresh_tokens_is_used
     ON refresh_tokens(is_used);
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_used_at
+    ON refresh_tokens(used_at);
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_family_id
+    ON refresh_tokens(family_id);
+
+CREATE TABLE IF NOT EXISTS login_attempts (
+    key text PRIMARY KEY,
+    failures int NOT NULL DEFAULT 0,
+    last_failure_at timestamptz NOT NULL DEFAULT NOW(),
+    locked_until timestamptz
+);
+
+-- Not tied to user_profile, since events can concern usernames
+-- that don't exist and should outlive deleted users
+CREATE TABLE IF NOT EXISTS audit_log (
+    id uuid PRIMARY KEY,
+    event text NOT NULL,
+    username text,
+    client_ip text,
+    detail text,
+    created_at timestamptz NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_username
+    ON audit_log(username);
+
+CREATE TABLE IF NOT EXISTS totp_credentials (
+    username text PRIMARY KEY REFERENCES user_profile(username)
+        ON DELETE CASCADE,
+    secret text NOT NULL,
+    enabled boolean NOT NULL DEFAULT FALSE,
+    last_used_step bigint,
+    created_at timestamptz NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    username text NOT NULL REFERENCES user_profile(username)
+        ON DELETE CASCADE,
+    code_hash text NOT NULL,
+    used_at timestamptz,
+    PRIMARY KEY(username, code_hash)
+);
@@ -1,8 +1,8 @@
 use anyhow::Result;
-use sqlx::PgPool;
+use sqlx::{types::Json, PgPool};
 use uuid::Uuid;
 
-use models::{ContentType, Image, ImageInfo, ImageVersion};
+use models::{ContentType, Image, ImageInfo, ImageVersion, Operation};
 
 /// Insert image record into the database.
 pub async fn insert_image(
@@ -11,17 +11,19 @@ pub async fn insert_image(
     name: &str,
     content_type: ContentType,
     username: &str,
+    object_key: &str,
 ) -> Result<()> {
     sqlx::query!(
         r#"
-        INSERT INTO image (id, name, content_type, username)
-        VALUES ($1, $2, $3, $4)
+        INSERT INTO image (id, name, content_type, username, object_key)
+        VALUES ($1, $2, $3, $4, $5)
         ON CONFLICT (name, username) DO NOTHING
         "#,
         id,
         name,
         content_type as i32,
         username,
+        object_key,
     )
     .execute(db)
     .await?;
@@ -36,13 +38,16 @@ pub async fn insert_image_version(
     version: &str,
     dimensions: (u32, u32),
     size: usize,
+    content_type: ContentType,
+    operations: Option<&[Operation]>,
 ) -> Result<()> {
     let version_option: Option<String> = sqlx::query_scalar!(
         r#"
         INSERT INTO image_version (
-            image_id, version, current, width, height, size
+            image_id, version, current, width, height, size,
+            content_type, operations
         )
-        VALUES ($1, $2, TRUE, $3, $4, $5)
+        VALUES ($1, $2, TRUE, $3, $4, $5, $6, $7)
         RETURNING version
         "#,
         image_id,
@@ -50,6 +55,8 @@ pub async fn insert_image_version(
         dimensions.0 as i32,
         dimensions.1 as i32,
         size as i64,
+        content_type as i32,
+        operations.map(Json) as Option<Json<&[Operation]>>,
     )
     .fetch_optional(db)
     .await?;
@@ -68,7 +75,8 @@ pub async fn find_image(
 ) -> Result<Option<ImageInfo>> {
     let image = sqlx::query_as::<_, ImageInfo>(
         r#"
-        SELECT i.id, i.name, i.username, i.content_type, v.version
+        SELECT i.id, i.name, i.username, i.object_key, v.version,
+            COALESCE(v.content_type, i.content_type) AS content_type
         FROM image AS i
         LEFT JOIN image_version AS v
             ON v.image_id = i.id
@@ -115,12 +123,13 @@ pub async fn find_image_with_version_info(
             SELECT COUNT(1) AS version_count
             FROM versions
         )
-        SELECT i.id, i.name, i.content_type, i.created_at,
+        SELECT i.id, i.name, i.created_at, i.object_key,
+            COALESCE(v.content_type, i.content_type) AS content_type,
             v.ts AS last_modified, v.version,
             v.width, v.height, v.size, vc.version_count,
             v.idx AS version_index,
             v.idx = vc.version_count AS latest_version,
-            v.idx = 1 AS initial_version
+            v.idx = 1 AS initial_version, v.operations
         FROM image_info AS i
         LEFT JOIN current_version AS v
             ON TRUE
@@ -181,12 +190,13 @@ pub async fn find_all_images(
             FROM versions
             GROUP BY image_id
         )
-        SELECT i.id, i.name, i.content_type, i.created_at,
+        SELECT i.id, i.name, i.created_at, i.object_key,
+            COALESCE(v.content_type, i.content_type) AS content_type,
             v.ts AS last_modified, v.version,
             v.width, v.height, v.size, vc.version_count,
             v.idx AS version_index,
             v.idx = vc.version_count AS latest_version,
-            v.idx = 1 AS initial_version
+            v.idx = 1 AS initial_version, v.operations
         FROM images AS i
         LEFT JOIN current_version AS v
             ON v.image_id = i.id
@@ -216,6 +226,21 @@ pub async fn delete_image(
     Ok(())
 }
 
+/// Delete all of a user's images, along with their versions.
+pub async fn delete_all_images(
+    db: &PgPool,
+    username: &str,
+) -> Result<()> {
+    sqlx::query!(
+        "DELETE FROM image WHERE username = $1",
+        username,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
 /// Revert an image to its previous version.
 pub async fn revert_image_version(
     db: &PgPool,
@@ -284,20 +309,23 @@ pub async fn restore_image_version(
     Ok(None)
 }
 
-/// Update the name of an image.
+/// Update the name of an image, first storing its object key
+/// if it was still being derived from the old name.
 pub async fn rename_image(
     db: &PgPool,
     image_id: &Uuid,
     new_name: &str,
+    object_key: &str,
 ) -> Result<Option<String>> {
     let image_name: Option<String> = sqlx::query_scalar!(
         r#"
-        UPDATE image SET name = $1
+        UPDATE image SET name = $1, object_key = COALESCE(object_key, $3)
         WHERE id = $2
         RETURNING name
         "#,
         new_name,
         image_id,
+        object_key,
     )
     .fetch_one(db)
     .await?;
@@ -305,6 +333,56 @@ pub async fn rename_image(
     Ok(image_name)
 }
 
+/// Change the content type of an image after its format is
+/// converted, renaming it to match unless the new name is taken.
+pub async fn update_image_format(
+    db: &PgPool,
+    image_id: &Uuid,
+    content_type: ContentType,
+    new_name: &str,
+    object_key: &str,
+) -> Result<()> {
+    // Pin the content type of versions saved before
+    // per-version content types were recorded
+    sqlx::query!(
+        r#"
+        UPDATE image_version SET content_type = (
+            SELECT content_type FROM image WHERE id = $1
+        )
+        WHERE image_id = $1 AND content_type IS NULL
+        "#,
+        image_id,
+    )
+    .execute(db)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE image AS i SET
+            content_type = $2,
+            object_key = COALESCE(i.object_key, $4),
+            name = CASE
+                WHEN EXISTS (
+                    SELECT 1 FROM image
+                    WHERE name = $3
+                        AND username = i.username
+                        AND id <> i.id
+                ) THEN i.name
+                ELSE $3
+            END
+        WHERE i.id = $1
+        "#,
+        image_id,
+        content_type as i32,
+        new_name,
+        object_key,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
 /// Unset the `current` flag for any old versions of an image.
 async fn unset_current_version_flags(
     db: &PgPool,
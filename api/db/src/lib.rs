@@ -1,5 +1,7 @@
 mod conn;
 pub mod images;
+pub mod users;
 
 pub use conn::create_conn_pool;
 pub use images::*;
+pub use users::*;
@@ -0,0 +1,17 @@
+use sqlx::{Error as SqlxError, PgPool};
+
+/// Delete a user. Their images, image versions, refresh tokens, and
+/// TOTP credentials go with them through foreign key cascades.
+pub async fn delete_user(
+    db: &PgPool,
+    username: &str,
+) -> Result<(), SqlxError> {
+    sqlx::query!(
+        "DELETE FROM user_profile WHERE username = $1",
+        username,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
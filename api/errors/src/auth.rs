@@ -1,9 +1,25 @@
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Json, Response},
 };
 use serde_json::json;
 
+/// A problem with one field of user input.
+#[derive(Debug)]
+pub struct FieldError {
+    pub field: &'static str,
+
+    /// Short machine-readable reason, e.g. `too_short`
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, code: &'static str, message: impl Into<String>) -> Self {
+        Self { field, code, message: message.into() }
+    }
+}
+
 #[derive(Debug)]
 pub enum AuthError {
     MissingCredentials,
@@ -11,12 +27,26 @@ pub enum AuthError {
     InvalidToken,
     TokenCreationFailure,
     RefreshTokenNotSaved,
-    InvalidUserInput,
+    InvalidUserInput(Vec<FieldError>),
     UsernameTaken,
     UserCreationFailure,
     UserNotFound,
     BadOrMissingHeader,
     QueryFailure,
+    PasswordHashFailure,
+    TotpAlreadyEnabled,
+    TotpNotEnrolled,
+    InvalidTotpCode,
+    SessionNotFound,
+    InvalidCsrfToken,
+
+    /// Too many failed logins from the client; retry after the
+    /// given number of seconds
+    TooManyAttempts(u64),
+
+    /// Too many failed logins for the account; retry after the
+    /// given number of seconds
+    AccountLocked(u64),
 }
 
 impl IntoResponse for AuthError {
@@ -37,8 +67,24 @@ impl IntoResponse for AuthError {
             AuthError::RefreshTokenNotSaved => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Failed to store refresh token")
             }
-            AuthError::InvalidUserInput => {
-                (StatusCode::BAD_REQUEST, "Invalid user info")
+            AuthError::InvalidUserInput(fields) => {
+                let fields: Vec<_> = fields
+                    .into_iter()
+                    .map(|f| json!({ "field": f.field, "code": f.code, "message": f.message }))
+                    .collect();
+
+                let body = Json(json!({ "error": "Invalid user info", "fields": fields }));
+                return (StatusCode::BAD_REQUEST, body).into_response();
+            }
+            AuthError::TooManyAttempts(retry_after) => {
+                let body = Json(json!({ "error": "Too many failed login attempts" }));
+                let headers = [(header::RETRY_AFTER, retry_after.to_string())];
+                return (StatusCode::TOO_MANY_REQUESTS, headers, body).into_response();
+            }
+            AuthError::AccountLocked(retry_after) => {
+                let body = Json(json!({ "error": "Account temporarily locked" }));
+                let headers = [(header::RETRY_AFTER, retry_after.to_string())];
+                return (StatusCode::LOCKED, headers, body).into_response();
             }
             AuthError::UsernameTaken => {
                 (StatusCode::CONFLICT, "Username already in use")
@@ -55,6 +101,24 @@ impl IntoResponse for AuthError {
             AuthError::QueryFailure => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Error querying user data")
             }
+            AuthError::PasswordHashFailure => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "Error hashing or verifying password")
+            }
+            AuthError::TotpAlreadyEnabled => {
+                (StatusCode::CONFLICT, "Two-factor authentication is already enabled")
+            }
+            AuthError::TotpNotEnrolled => {
+                (StatusCode::BAD_REQUEST, "Two-factor authentication hasn't been set up")
+            }
+            AuthError::InvalidTotpCode => {
+                (StatusCode::UNAUTHORIZED, "Invalid authentication code")
+            }
+            AuthError::SessionNotFound => {
+                (StatusCode::NOT_FOUND, "Session not found")
+            }
+            AuthError::InvalidCsrfToken => {
+                (StatusCode::FORBIDDEN, "Missing or invalid CSRF token")
+            }
         };
 
         let body = Json(json!({ "error": error_message }));
@@ -9,10 +9,15 @@ pub enum ImageError {
     MissingMultipartField,
     InvalidFileType,
     ReadFailure,
-    S3OperationFailure(String),
+    StorageFailure(String),
     QueryFailure(String),
     NotFound,
     UserNotFound,
+    UserMismatch,
+    InvalidTransform(String),
+    InvalidFilter(String),
+    InvalidThumbnailSize,
+    ProcessingFailure,
 }
 
 impl IntoResponse for ImageError {
@@ -42,10 +47,10 @@ impl IntoResponse for ImageError {
                     "Error reading image".to_string(),
                 )
             }
-            ImageError::S3OperationFailure(e) => {
+            ImageError::StorageFailure(e) => {
                 (
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    format!("S3 operation failed: {}", e),
+                    format!("Object storage operation failed: {}", e),
                 )
             }
             ImageError::QueryFailure(e) => {
@@ -66,6 +71,36 @@ impl IntoResponse for ImageError {
                     "User not found".to_string(),
                 )
             }
+            ImageError::UserMismatch => {
+                (
+                    StatusCode::FORBIDDEN,
+                    "Upload user doesn't match the authenticated user".to_string(),
+                )
+            }
+            ImageError::InvalidTransform(e) => {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid transform: {}", e),
+                )
+            }
+            ImageError::InvalidFilter(e) => {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid filter: {}", e),
+                )
+            }
+            ImageError::InvalidThumbnailSize => {
+                (
+                    StatusCode::BAD_REQUEST,
+                    "Unsupported thumbnail size".to_string(),
+                )
+            }
+            ImageError::ProcessingFailure => {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Error processing image".to_string(),
+                )
+            }
         };
 
         let body = Json(serde_json::json!({ "error": error_message }));
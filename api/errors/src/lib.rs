@@ -1,5 +1,5 @@
 pub mod auth;
 pub mod image;
 
-pub use auth::AuthError;
+pub use auth::{AuthError, FieldError};
 pub use image::ImageError;
@@ -3,27 +3,60 @@ use axum::{
         connect_info::ConnectInfo,
         State,
     },
-    response::Json,
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
-use std::net::SocketAddr;
+use axum_extra::extract::cookie::CookieJar;
+use std::net::{IpAddr, SocketAddr};
 use tracing::{error, info};
 
 use auth::{
+    client_ip::ClientIp,
+    cookies,
+    keys::{self, PublicKeySet},
     middleware::RequireAuth,
     jwt,
+    throttle,
+    validation,
 };
 use errors::AuthError;
-use models::{User, UserInfo};
+use models::{AuditEvent, RefreshToken, User, UserInfo};
+use repos::UserRepoError;
 use schemas::{
-    LoginResponse, UserResponse,
+    LoginResponse, MfaChallengeResponse, UserResponse,
     RefreshTokenRequest, RefreshTokenResponse,
     LogoutResponse,
+    ChangePasswordRequest, ChangePasswordResponse,
+    DeleteUserRequest, DeleteUserResponse,
 };
 use state::AppState;
 
 /// Result returning AuthError on errors.
 type Result<T> = anyhow::Result<T, AuthError>;
 
+/// Longest user agent kept for a session
+const MAX_USER_AGENT_LENGTH: usize = 256;
+
+/// The client a session was started by, shown in its session list.
+pub(crate) struct SessionClient {
+    user_agent: Option<String>,
+    client_ip: Option<String>,
+}
+
+impl SessionClient {
+    pub(crate) fn new(headers: &HeaderMap, client_ip: IpAddr) -> Self {
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect());
+
+        Self {
+            user_agent,
+            client_ip: Some(client_ip.to_string()),
+        }
+    }
+}
+
 /// Handler for user registration route.
 pub async fn register(
     State(state): State<AppState>,
@@ -32,43 +65,58 @@ pub async fn register(
 ) -> Result<Json<UserResponse>> {
     info!("Client {addr} is attempting to register");
 
-    // Check if username is already taken
+    validation::validate_new_user(&payload)?;
+
+    // Check if username is already taken, in any case
     if state
         .user_repo
-        .find(&payload.username)
+        .username_taken(&payload.username)
         .await
         .map_err(|_| AuthError::QueryFailure)?
-        .is_some()
     {
         return Err(AuthError::UsernameTaken);
     }
 
-    // Create user
+    // Create user. Concurrent registrations of the same username
+    // can all pass the check above, and all but one fail here.
     let user: UserInfo = state
         .user_repo
         .create(&payload)
         .await
-        .map_err(|_| AuthError::UserCreationFailure)?;
+        .map_err(|e| match e {
+            UserRepoError::Query(_) => AuthError::UserCreationFailure,
+            e => user_repo_failure(e),
+        })?;
 
     Ok(Json(UserResponse { user }))
 }
 
-/// Handler for user login route.
+/// Handler for user login route. Users with two-factor
+/// authentication get a challenge token instead of tokens.
 pub async fn login(
     State(state): State<AppState>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    jar: CookieJar,
     Json(payload): Json<User>,
-) -> Result<Json<LoginResponse>> {
-    info!("Client {addr} is attempting to log in");
+) -> Result<Response> {
+    info!("Client {client_ip} is attempting to log in");
+
+    if payload.username.is_empty() || payload.password.is_empty() {
+        return Err(AuthError::MissingCredentials);
+    }
+
+    throttle::check(&state, &payload.username, client_ip).await?;
 
     // Verify user credentials
     let pw_valid = state
         .user_repo
         .authorize(&payload)
         .await
-        .map_err(|_| AuthError::QueryFailure)?;
+        .map_err(user_repo_failure)?;
 
     if !pw_valid {
+        throttle::record_failure(&state, &payload.username, client_ip).await?;
         return Err(AuthError::InvalidCredentials);
     }
 
@@ -80,18 +128,31 @@ pub async fn login(
         .map_err(|_| AuthError::QueryFailure)?
         .ok_or(AuthError::UserNotFound)?;
 
-    // Generate access and refresh tokens
-    let (access_token, refresh_token) = create_tokens(
-        &payload.username,
-        state,
-    )
-    .await?;
+    let totp_enabled = state
+        .totp_repo
+        .find(&user.username)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?
+        .is_some_and(|credential| credential.enabled);
+
+    // Failed attempts are only forgotten once the second factor
+    // is given too, so they can't be reset with the password alone
+    if totp_enabled {
+        let challenge_token = jwt::create_challenge_token(&user.username)
+            .map_err(|_| AuthError::TokenCreationFailure)?;
 
-    Ok(Json(LoginResponse {
-        user,
-        access_token,
-        refresh_token,
-    }))
+        let response = MfaChallengeResponse {
+            mfa_required: true,
+            challenge_token,
+        };
+        return Ok(Json(response).into_response());
+    }
+
+    throttle::record_success(&state, &user.username).await?;
+
+    let client = SessionClient::new(&headers, client_ip);
+    let response = login_response(user, state, &client).await?;
+    Ok(token_response(&headers, jar, response))
 }
 
 /// Handler for current user identification route.
@@ -101,36 +162,148 @@ pub async fn current_user(
     Ok(Json(UserResponse { user }))
 }
 
+/// Handler for password change route. Every refresh token of the
+/// user is revoked, so all sessions must log in again once their
+/// access tokens expire.
+pub async fn change_password(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    RequireAuth(user): RequireAuth,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<Json<ChangePasswordResponse>> {
+    info!("Client {client_ip} is attempting to change the password of {}", user.username);
+
+    verify_password(&state, &user, &payload.current_password, client_ip).await?;
+    validation::validate_new_password(&payload.new_password)?;
+
+    state
+        .user_repo
+        .update_password(&user.username, &payload.new_password)
+        .await
+        .map_err(user_repo_failure)?;
+
+    state
+        .refresh_token_repo
+        .delete_all_user_tokens(&user.username)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    Ok(Json(ChangePasswordResponse {
+        message: "Password changed successfully".to_string(),
+    }))
+}
+
+/// Handler for account deletion route, which deletes the user
+/// and all of their images.
+pub async fn delete_user(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    RequireAuth(user): RequireAuth,
+    Json(payload): Json<DeleteUserRequest>,
+) -> Result<Json<DeleteUserResponse>> {
+    info!("Client {client_ip} is attempting to delete user {}", user.username);
+
+    verify_password(&state, &user, &payload.password, client_ip).await?;
+
+    // Images go first, so that the account is only deleted once
+    // all of its objects are gone
+    state
+        .image_repo
+        .delete_all(user.clone())
+        .await
+        .map_err(|e| {
+            error!("Failed to delete images of user {}: {:?}", user.username, e);
+            AuthError::QueryFailure
+        })?;
+
+    state
+        .refresh_token_repo
+        .delete_all_user_tokens(&user.username)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    state
+        .user_repo
+        .delete(&user.username)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    Ok(Json(DeleteUserResponse {
+        message: "User deleted successfully".to_string(),
+    }))
+}
+
 /// Route for user logout.
 pub async fn logout(
     State(state): State<AppState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     RequireAuth(_user): RequireAuth,
-    Json(payload): Json<RefreshTokenRequest>,
-) -> Result<Json<LogoutResponse>> {
+    jar: CookieJar,
+    payload: Option<Json<RefreshTokenRequest>>,
+) -> Result<Response> {
     info!("Client {addr} is attempting to log out");
 
+    // Clients in cookie mode send no body
+    let (refresh_token, from_cookie) = match payload {
+        Some(Json(payload)) => (payload.refresh_token, false),
+        None => {
+            let token = jar
+                .get(cookies::REFRESH_TOKEN_COOKIE)
+                .ok_or(AuthError::InvalidToken)?
+                .value()
+                .to_string();
+            (token, true)
+        }
+    };
+
     // Delete given refresh token from database
     state
         .refresh_token_repo
-        .delete_token(&payload.refresh_token)
+        .delete_token(&refresh_token)
         .await
         .map_err(|_| AuthError::QueryFailure)?;
 
-    Ok(Json(LogoutResponse {
+    let response = Json(LogoutResponse {
         message: "Logged out successfully".to_string(),
-    }))
+    });
+
+    if from_cookie {
+        return Ok((cookies::clear_session_cookies(jar), response).into_response());
+    }
+
+    Ok(response.into_response())
 }
 
-/// Handler for token refresh route.
+/// Handler for token refresh route. Reusing a token revokes the
+/// session it belongs to, since either it or its replacement must
+/// have been stolen.
 pub async fn refresh(
     State(state): State<AppState>,
-    Json(payload): Json<RefreshTokenRequest>,
-) -> Result<Json<RefreshTokenResponse>> {
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    jar: CookieJar,
+    payload: Option<Json<RefreshTokenRequest>>,
+) -> Result<Response> {
+    // Clients in cookie mode send no body, and get new tokens as
+    // cookies too
+    let (refresh_token, from_cookie) = match payload {
+        Some(Json(payload)) => (payload.refresh_token, false),
+        None => {
+            let token = jar
+                .get(cookies::REFRESH_TOKEN_COOKIE)
+                .ok_or(AuthError::InvalidToken)?
+                .value()
+                .to_string();
+
+            cookies::check_csrf(&Method::POST, &headers, &jar)?;
+            (token, true)
+        }
+    };
+
     // Look up refresh token in database
     let token_obj = state
         .refresh_token_repo
-        .find_by_token(&payload.refresh_token)
+        .find_by_token(&refresh_token)
         .await
         .map_err(|_| AuthError::QueryFailure)?
         .ok_or(AuthError::InvalidToken)?;
@@ -140,7 +313,7 @@ pub async fn refresh(
         // Delete token
         state
             .refresh_token_repo
-            .delete_token(&payload.refresh_token)
+            .delete_token(&refresh_token)
             .await
             .map_err(|_| AuthError::QueryFailure)?;
 
@@ -150,49 +323,181 @@ pub async fn refresh(
 
     // Check if token is already used
     if token_obj.is_used {
-        error!("Token reuse detected:");
-        error!(
-            "User {} originally used token {} at {:?}",
-            &token_obj.username,
-            &payload.refresh_token,
-            &token_obj.used_at,
-        );
-        error!("Deleting all tokens for user {}", &token_obj.username);
-
-        // Delete all refresh tokens for this token's user
-        state
-            .refresh_token_repo
-            .delete_all_user_tokens(&token_obj.username)
-            .await
-            .map_err(|_| AuthError::QueryFailure)?;
+        revoke_reused_family(&state, &token_obj, client_ip).await?;
+        return Err(AuthError::InvalidToken);
+    }
+
+    // Mark the token as used. This fails if a concurrent refresh
+    // used it since it was looked up, which is reuse too.
+    let is_marked = state
+        .refresh_token_repo
+        .mark_token_used(&refresh_token)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
 
+    if !is_marked {
+        revoke_reused_family(&state, &token_obj, client_ip).await?;
         return Err(AuthError::InvalidToken);
     }
 
-    // Mark token as used
+    // Record the token's final use, which is when its session was
+    // last used until the new token is
     state
         .refresh_token_repo
-        .mark_token_used(&payload.refresh_token)
+        .update_last_used(&refresh_token)
         .await
         .map_err(|_| AuthError::QueryFailure)?;
 
-    // Generate new access and refresh tokens
-    let (access_token, new_refresh_token) = create_tokens(
-        &token_obj.username,
-        state,
-    )
-    .await?;
+    // Generate new access and refresh tokens, continuing the
+    // token's session
+    let access_token = jwt::create_access_token(&token_obj.username)
+        .map_err(|_| AuthError::TokenCreationFailure)?;
+    let new_refresh_token = jwt::create_refresh_token();
+
+    state
+        .refresh_token_repo
+        .rotate_token(&token_obj, &new_refresh_token)
+        .await
+        .map_err(|_| AuthError::RefreshTokenNotSaved)?;
+
+    if from_cookie {
+        let jar = cookies::set_session_cookies(jar, access_token, new_refresh_token);
+        return Ok((StatusCode::NO_CONTENT, jar).into_response());
+    }
 
     Ok(Json(RefreshTokenResponse {
         access_token,
         refresh_token: new_refresh_token,
-    }))
+    })
+    .into_response())
+}
+
+/// Revoke the session a reused refresh token belongs to, and
+/// write the reuse to the audit log.
+async fn revoke_reused_family(
+    state: &AppState,
+    token_obj: &RefreshToken,
+    client_ip: IpAddr,
+) -> Result<()> {
+    error!("Token reuse detected:");
+    error!(
+        "User {} originally used token {} at {:?}",
+        &token_obj.username,
+        &token_obj.id,
+        &token_obj.used_at,
+    );
+    error!(
+        "Revoking session {} of user {}",
+        &token_obj.family_id,
+        &token_obj.username,
+    );
+
+    // Delete every token of the session this token belongs to
+    state
+        .refresh_token_repo
+        .delete_family(token_obj.family_id)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    let detail = format!(
+        "Session {} revoked after reuse of a refresh token used at {}",
+        token_obj.family_id,
+        token_obj.used_at.map_or("an unknown time".to_string(), |t| t.to_rfc3339()),
+    );
+    let event = AuditEvent::new(
+        "refresh_token_reuse",
+        Some(&token_obj.username),
+        Some(client_ip),
+        detail,
+    );
+    if let Err(e) = state.audit_log_repo.record(&event).await {
+        error!("Failed to write audit log: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Handler for the JWKS route, which publishes the public keys
+/// access tokens can be verified with. It's empty when tokens are
+/// signed with a shared secret.
+pub async fn jwks() -> Json<&'static PublicKeySet> {
+    Json(&keys::get_keys().jwks)
+}
+
+/// Map a user repository error to the response for it. A stored
+/// hash that can't be checked is logged, since it needs fixing.
+fn user_repo_failure(e: UserRepoError) -> AuthError {
+    match e {
+        UserRepoError::PasswordHash(e) => {
+            error!("{}", e);
+            AuthError::PasswordHashFailure
+        }
+        UserRepoError::UsernameTaken => AuthError::UsernameTaken,
+        UserRepoError::Query(_) => AuthError::QueryFailure,
+    }
+}
+
+/// Check the password of an authenticated user, for routes that
+/// need it confirmed. Guesses are throttled like logins, so a stolen
+/// session can't be used to find the password.
+async fn verify_password(
+    state: &AppState,
+    user: &UserInfo,
+    password: &str,
+    client_ip: IpAddr,
+) -> Result<()> {
+    throttle::check(state, &user.username, client_ip).await?;
+
+    let credentials = User {
+        username: user.username.clone(),
+        password: password.to_string(),
+    };
+
+    let pw_valid = state
+        .user_repo
+        .authorize(&credentials)
+        .await
+        .map_err(user_repo_failure)?;
+
+    if !pw_valid {
+        throttle::record_failure(state, &user.username, client_ip).await?;
+        return Err(AuthError::InvalidCredentials);
+    }
+
+    throttle::record_success(state, &user.username).await
+}
+
+/// Generate tokens for a user who has logged in.
+pub(crate) async fn login_response(
+    user: UserInfo,
+    state: AppState,
+    client: &SessionClient,
+) -> Result<LoginResponse> {
+    let (access_token, refresh_token) = create_tokens(&user.username, state, client).await?;
+
+    Ok(LoginResponse {
+        user,
+        access_token,
+        refresh_token,
+    })
+}
+
+/// Respond to a login with its tokens, or for clients in cookie
+/// mode, with the user and the tokens in cookies.
+pub(crate) fn token_response(headers: &HeaderMap, jar: CookieJar, login: LoginResponse) -> Response {
+    if cookies::wants_cookies(headers) {
+        let jar = cookies::set_session_cookies(jar, login.access_token, login.refresh_token);
+        return (jar, Json(UserResponse { user: login.user })).into_response();
+    }
+
+    Json(login).into_response()
 }
 
 /// Generate and return access- and refresh- tokens.
 async fn create_tokens(
     username: &str,
     state: AppState,
+    client: &SessionClient,
 ) -> Result<(String, String)> {
     // Generate JWT access token
     let access_token = jwt::create_access_token(username)
@@ -204,7 +509,12 @@ async fn create_tokens(
     // Save refresh token to database
     state
         .refresh_token_repo
-        .create_token(username, &refresh_token)
+        .create_token(
+            username,
+            &refresh_token,
+            client.user_agent.as_deref(),
+            client.client_ip.as_deref(),
+        )
         .await
         .map_err(|_| AuthError::RefreshTokenNotSaved)?;
 
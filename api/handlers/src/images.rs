@@ -5,24 +5,29 @@ use axum::{
         multipart::Field,
         Multipart, Path, Query, State,
     },
-    http::header,
-    response::{Json, Response},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Json, Response},
 };
 use image::ImageReader;
 use std::io::Cursor;
 use std::net::SocketAddr;
-use tracing::info;
+use tracing::{info, warn};
 
+use crate::negotiation::{is_transcodable, preferred_format};
 use auth::middleware::RequireAuth;
 use errors::ImageError;
 use models::{
-    ContentType, Image, ImageData, ImageList,
-    UploadImage, UserInfo,
+    ContentType, Image, ImageData, ImageList, Operation,
+    ResizeOptions, UploadImage,
 };
 use schemas::{
     ImageRenameRequest,
     ImageUpdateResponse,
     PaginationParams,
+    RenditionParams,
+    ThumbnailParams,
+    TransformParams,
+    TransformRequest,
 };
 use state::AppState;
 
@@ -38,7 +43,7 @@ pub async fn get_all_images_metadata(
     info!("Client {addr} requested images");
 
     let page = params.page.max(1);
-    let limit = params.limit.max(1).min(100);
+    let limit = params.limit.clamp(1, 100);
 
     let images: ImageList = state
         .image_repo
@@ -48,27 +53,95 @@ pub async fn get_all_images_metadata(
     Ok(Json(images))
 }
 
-/// Route for retrieving data for a specific image.
+/// Route for retrieving data for a specific image, optionally
+/// resized and/or converted to another format.
+///
+/// Without an explicit format, a JPEG or PNG image is transcoded to
+/// WebP or AVIF if the client lists either in its `Accept` header.
 pub async fn get_image(
     State(state): State<AppState>,
     RequireAuth(user): RequireAuth,
     Path(image_id): Path<String>,
+    Query(params): Query<RenditionParams>,
+    headers: HeaderMap,
 ) -> Result<Response> {
-    let image: ImageData = state
+    let resize = (params.w.is_some() || params.h.is_some()).then(|| {
+        ResizeOptions {
+            width: params.w,
+            height: params.h,
+            fit: params.fit,
+            filter: Default::default(),
+        }
+    });
+
+    let format = match (params.format, preferred_format(&headers)) {
+        (Some(format), _) => Some(format),
+
+        // The image's format is only looked up if the client
+        // accepts one it could be transcoded to
+        (None, Some(preferred)) => state
+            .image_repo
+            .get_metadata_for_one(&image_id, user.clone())
+            .await?
+            .filter(|image| is_transcodable(&ContentType::from_str(&image.content_type)))
+            .map(|_| preferred),
+        (None, None) => None,
+    };
+
+    let image: ImageData = if resize.is_some() || format.is_some() {
+        state
+            .image_repo
+            .get_rendition(&image_id, resize, format, user)
+            .await?
+    } else {
+        state
+            .image_repo
+            .get_one(&image_id, user)
+            .await?
+    }
+    .ok_or(ImageError::NotFound)?;
+
+    let response = Response::builder()
+        .header(header::CONTENT_TYPE, image.content_type)
+        .header(header::VARY, "Accept")
+        .header(
+            header::CACHE_CONTROL,
+            "no-store, no-cache, must-revalidate, proxy-revalidate",
+        )
+        .header(header::PRAGMA, "no-cache")
+        .header(header::EXPIRES, "0")
+        .body(Body::from(image.data))
+        .unwrap();
+
+    Ok(response)
+}
+
+/// Route for retrieving a thumbnail of a specific image.
+pub async fn get_image_thumbnail(
+    State(state): State<AppState>,
+    RequireAuth(user): RequireAuth,
+    Path(image_id): Path<String>,
+    Query(params): Query<ThumbnailParams>,
+) -> Result<Response> {
+    if !transform::THUMBNAIL_SIZES.contains(&params.size) {
+        return Err(ImageError::InvalidThumbnailSize);
+    }
+
+    let thumbnail: ImageData = state
         .image_repo
-        .get_one(&image_id, user)
+        .get_thumbnail(&image_id, params.size, user)
         .await?
         .ok_or(ImageError::NotFound)?;
 
     let response = Response::builder()
-        .header(header::CONTENT_TYPE, image.content_type)
+        .header(header::CONTENT_TYPE, thumbnail.content_type)
         .header(
             header::CACHE_CONTROL,
             "no-store, no-cache, must-revalidate, proxy-revalidate",
         )
         .header(header::PRAGMA, "no-cache")
         .header(header::EXPIRES, "0")
-        .body(Body::from(image.data))
+        .body(Body::from(thumbnail.data))
         .unwrap();
 
     Ok(response)
@@ -89,15 +162,16 @@ pub async fn get_image_metadata(
     Ok(Json(image))
 }
 
-/// Route for uploading images.
+/// Route for uploading images. The images are owned by the
+/// authenticated user; a `user` field, if given, must name them.
 pub async fn upload_images(
     State(state): State<AppState>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    RequireAuth(user): RequireAuth,
     mut multipart: Multipart,
 ) -> Result<Response> {
     info!("Client {addr} added image");
 
-    let mut username = String::new();
     let mut images: Vec<UploadImage> = Vec::new();
 
     while let Some(field) = multipart
@@ -107,10 +181,18 @@ pub async fn upload_images(
     {
         match field.name().unwrap_or("") {
             "user" => {
-                username = field
+                let username = field
                     .text()
                     .await
                     .map_err(|_| ImageError::MissingMultipartField)?;
+
+                if username != user.username {
+                    warn!(
+                        "Client {addr} authenticated as {} tried to upload as {}",
+                        user.username, username,
+                    );
+                    return Err(ImageError::UserMismatch);
+                }
             }
             "files[]" => {
                 let content_type = ContentType::from_str(
@@ -130,25 +212,17 @@ pub async fn upload_images(
         }
     }
 
-    if !username.is_empty() {
-        // Find the user record
-        let user: UserInfo = state
-            .user_repo
-            .find(&username)
-            .await
-            .map_err(|e| ImageError::QueryFailure(e.to_string()))?
-            .ok_or(ImageError::UserNotFound)?;
-
-        // Upload the image to S3
-        state
-            .image_repo
-            .upload(images, user)
-            .await?;
-
-        return Ok(Response::default());
+    if images.is_empty() {
+        return Err(ImageError::MissingMultipartField);
     }
 
-    Err(ImageError::MissingMultipartField)
+    // Upload the images to the object store
+    state
+        .image_repo
+        .upload(images, user)
+        .await?;
+
+    Ok(Response::default())
 }
 
 /// Route for deleting an image.
@@ -211,6 +285,82 @@ pub async fn restore_image_version(
     Ok(Json(ImageUpdateResponse { updated }))
 }
 
+/// Route for applying a pipeline of operations to an image and
+/// saving the result as a new version, or, in preview mode,
+/// returning the rendered image without saving it.
+pub async fn process_image(
+    State(state): State<AppState>,
+    RequireAuth(user): RequireAuth,
+    Path(image_id): Path<String>,
+    Query(params): Query<TransformParams>,
+    Json(payload): Json<TransformRequest>,
+) -> Result<Response> {
+    let ImageData { name, content_type, data } = state
+        .image_repo
+        .get_one(&image_id, user.clone())
+        .await?
+        .ok_or(ImageError::NotFound)?;
+
+    let content_type = ContentType::from_str(&content_type);
+    let operations = payload.operations;
+
+    // Decoding and resampling are CPU-bound, so keep them
+    // off the async worker threads
+    if params.preview {
+        let output = tokio::task::spawn_blocking(move || {
+            transform::preview(&data, &content_type, &operations, params.max_size)
+        })
+        .await
+        .map_err(|_| ImageError::ProcessingFailure)??;
+
+        let response = Response::builder()
+            .header(header::CONTENT_TYPE, output.content_type.to_string())
+            .header(header::CACHE_CONTROL, "no-store")
+            .body(Body::from(output.data))
+            .unwrap();
+
+        return Ok(response);
+    }
+
+    // Saved versions have to be decodable for later edits
+    if output_content_type(&operations) == Some(ContentType::AVIF) {
+        return Err(ImageError::InvalidTransform(
+            "AVIF is only supported for previews".to_string(),
+        ));
+    }
+
+    let (output, operations) = tokio::task::spawn_blocking(move || {
+        transform::apply(&data, &content_type, &operations)
+            .map(|output| (output, operations))
+    })
+    .await
+    .map_err(|_| ImageError::ProcessingFailure)??;
+
+    // Upload the result as a new version of the image
+    let image = UploadImage {
+        name,
+        content_type: output.content_type,
+        data: output.data,
+        dimensions: output.dimensions,
+        operations: Some(operations),
+    };
+
+    state
+        .image_repo
+        .save_version(&image_id, image, user)
+        .await?;
+
+    Ok(Json(ImageUpdateResponse { updated: true }).into_response())
+}
+
+/// Content type the last conversion in a pipeline encodes to, if any.
+fn output_content_type(operations: &[Operation]) -> Option<ContentType> {
+    operations.iter().rev().find_map(|op| match op {
+        Operation::Convert(options) => Some(options.format.into()),
+        _ => None,
+    })
+}
+
 /// Parse multipart image data.
 async fn parse_image_data(
     field: Field<'_>,
@@ -223,5 +373,11 @@ async fn parse_image_data(
         .with_guessed_format()?
         .into_dimensions()?;
 
-    Ok(UploadImage { name, content_type, data, dimensions })
+    Ok(UploadImage {
+        name,
+        content_type,
+        data,
+        dimensions,
+        operations: None,
+    })
 }
@@ -1,8 +1,17 @@
 pub mod auth;
 pub mod images;
+pub mod negotiation;
+pub mod sessions;
+pub mod totp;
 
-pub use auth::{current_user, login, logout, register, refresh};
+pub use auth::{
+    change_password, current_user, delete_user, jwks, login, logout,
+    register, refresh,
+};
 pub use images::{
     delete_image, get_all_images_metadata, get_image, get_image_metadata,
-    rename_image, restore_image_version, revert_image_version, upload_images,
+    get_image_thumbnail, process_image, rename_image, restore_image_version,
+    revert_image_version, upload_images,
 };
+pub use sessions::{list_sessions, revoke_all_sessions, revoke_session};
+pub use totp::{enroll_totp, login_totp, verify_totp};
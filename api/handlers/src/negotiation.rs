@@ -0,0 +1,101 @@
+use axum::http::{header, HeaderMap};
+
+use models::{ContentType, OutputFormat};
+
+/// Formats a stored image may be transcoded to, in order of preference
+const PREFERRED_FORMATS: [(OutputFormat, &str); 2] = [
+    (OutputFormat::Avif, "image/avif"),
+    (OutputFormat::Webp, "image/webp"),
+];
+
+/// Whether images of a type may be transcoded. Only photographic
+/// formats are; GIFs would lose their animation and other formats
+/// are served as stored.
+pub fn is_transcodable(source: &ContentType) -> bool {
+    matches!(source, ContentType::JPEG | ContentType::PNG)
+}
+
+/// Pick a more efficient format to transcode images to based on
+/// the client's `Accept` header, if it supports one.
+pub fn preferred_format(headers: &HeaderMap) -> Option<OutputFormat> {
+    let accept = headers.get(header::ACCEPT)?.to_str().ok()?;
+    let ranges = parse_accept(accept);
+
+    let mut best: Option<(OutputFormat, f32)> = None;
+    for (format, media_type) in PREFERRED_FORMATS {
+        // Only explicitly listed types count, since browsers
+        // send wildcards whether or not they can decode these
+        let quality = ranges
+            .iter()
+            .find(|(range, _)| range.eq_ignore_ascii_case(media_type))
+            .map(|(_, q)| *q)
+            .unwrap_or(0.0);
+
+        if quality > 0.0 && best.is_none_or(|(_, q)| quality > q) {
+            best = Some((format, quality));
+        }
+    }
+
+    best.map(|(format, _)| format)
+}
+
+/// Split an `Accept` header into media ranges and their quality values.
+fn parse_accept(accept: &str) -> Vec<(&str, f32)> {
+    accept
+        .split(',')
+        .filter_map(|part| {
+            let mut params = part.split(';').map(str::trim);
+            let range = params.next().filter(|r| !r.is_empty())?;
+
+            let quality = params
+                .find_map(|p| p.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+
+            Some((range, quality.clamp(0.0, 1.0)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn accept(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_prefers_avif_over_webp() {
+        let headers = accept("image/avif,image/webp,image/apng,*/*;q=0.8");
+        assert_eq!(preferred_format(&headers), Some(OutputFormat::Avif));
+    }
+
+    #[test]
+    fn test_respects_quality_values() {
+        let headers = accept("image/avif;q=0.5, image/webp");
+        assert_eq!(preferred_format(&headers), Some(OutputFormat::Webp));
+    }
+
+    #[test]
+    fn test_excludes_zero_quality() {
+        let headers = accept("image/avif;q=0, image/*");
+        assert_eq!(preferred_format(&headers), None);
+    }
+
+    #[test]
+    fn test_no_preference_without_listed_formats() {
+        let headers = accept("image/png,image/*;q=0.8,*/*;q=0.5");
+        assert_eq!(preferred_format(&headers), None);
+        assert_eq!(preferred_format(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_skips_non_photographic_sources() {
+        assert!(is_transcodable(&ContentType::JPEG));
+        assert!(is_transcodable(&ContentType::PNG));
+        assert!(!is_transcodable(&ContentType::GIF));
+    }
+}
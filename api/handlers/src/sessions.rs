@@ -0,0 +1,91 @@
+use axum::{
+    extract::{
+        connect_info::ConnectInfo,
+        Path, State,
+    },
+    response::Json,
+};
+use std::net::SocketAddr;
+use tracing::info;
+use uuid::Uuid;
+
+use auth::middleware::RequireAuth;
+use errors::AuthError;
+use models::Session;
+use schemas::{LogoutResponse, SessionsResponse};
+use state::AppState;
+
+/// Result returning AuthError on errors.
+type Result<T> = anyhow::Result<T, AuthError>;
+
+/// Most security events listed with sessions
+const SECURITY_EVENT_LIMIT: i64 = 20;
+
+/// Handler for listing the user's active sessions, i.e. their
+/// unused and unexpired refresh tokens, and recent security events.
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    RequireAuth(user): RequireAuth,
+) -> Result<Json<SessionsResponse>> {
+    let sessions = state
+        .refresh_token_repo
+        .find_active_by_username(&user.username)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?
+        .into_iter()
+        .map(Session::from)
+        .collect();
+
+    let security_events = state
+        .audit_log_repo
+        .find_by_username(&user.username, SECURITY_EVENT_LIMIT)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    Ok(Json(SessionsResponse { sessions, security_events }))
+}
+
+/// Handler for revoking one of the user's sessions. Its access
+/// token stays valid until it expires.
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    RequireAuth(user): RequireAuth,
+    Path(session_id): Path<Uuid>,
+) -> Result<Json<LogoutResponse>> {
+    info!("Client {addr} is attempting to revoke session {session_id} of {}", user.username);
+
+    let is_deleted = state
+        .refresh_token_repo
+        .delete_user_family(&user.username, session_id)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    if !is_deleted {
+        return Err(AuthError::SessionNotFound);
+    }
+
+    Ok(Json(LogoutResponse {
+        message: "Session revoked successfully".to_string(),
+    }))
+}
+
+/// Handler for logging out everywhere, which revokes every session
+/// of the user, including the current one.
+pub async fn revoke_all_sessions(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    RequireAuth(user): RequireAuth,
+) -> Result<Json<LogoutResponse>> {
+    info!("Client {addr} is attempting to log {} out everywhere", user.username);
+
+    state
+        .refresh_token_repo
+        .delete_all_user_tokens(&user.username)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    Ok(Json(LogoutResponse {
+        message: "Logged out of all sessions successfully".to_string(),
+    }))
+}
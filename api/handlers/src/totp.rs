@@ -0,0 +1,183 @@
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::{Json, Response},
+};
+use axum_extra::extract::cookie::CookieJar;
+use tracing::{error, info};
+
+use auth::{
+    client_ip::ClientIp,
+    middleware::RequireAuth,
+    jwt, throttle, totp,
+};
+use errors::AuthError;
+use models::AuditEvent;
+use schemas::{
+    RecoveryCodesResponse, TotpCodeRequest,
+    TotpEnrollmentResponse, TotpLoginRequest,
+};
+use state::AppState;
+
+use crate::auth::{login_response, token_response, SessionClient};
+
+/// Result returning AuthError on errors.
+type Result<T> = anyhow::Result<T, AuthError>;
+
+/// Handler for TOTP enrollment route. The new secret takes effect
+/// once a code for it is verified.
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    RequireAuth(user): RequireAuth,
+) -> Result<Json<TotpEnrollmentResponse>> {
+    let existing = state
+        .totp_repo
+        .find(&user.username)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    if existing.is_some_and(|credential| credential.enabled) {
+        return Err(AuthError::TotpAlreadyEnabled);
+    }
+
+    let secret = totp::generate_secret();
+    let otpauth_uri = totp::otpauth_uri(&secret, &user.username)
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    state
+        .totp_repo
+        .save_pending(&user.username, &secret)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    Ok(Json(TotpEnrollmentResponse { secret, otpauth_uri }))
+}
+
+/// Handler for TOTP verification route, which enables two-factor
+/// authentication given a first valid code and returns recovery
+/// codes. The codes aren't stored, so they can't be shown again.
+pub async fn verify_totp(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    RequireAuth(user): RequireAuth,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<Json<RecoveryCodesResponse>> {
+    let credential = state
+        .totp_repo
+        .find(&user.username)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?
+        .ok_or(AuthError::TotpNotEnrolled)?;
+
+    if credential.enabled {
+        return Err(AuthError::TotpAlreadyEnabled);
+    }
+
+    let step = totp::verify_code(&credential.secret, &user.username, &payload.code, None)
+        .map_err(|_| AuthError::QueryFailure)?
+        .ok_or(AuthError::InvalidTotpCode)?;
+
+    let recovery_codes = totp::generate_recovery_codes();
+    let hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|code| totp::hash_recovery_code(code))
+        .collect();
+
+    state
+        .totp_repo
+        .enable(&user.username, step, &hashes)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?;
+
+    info!("User {} enabled two-factor authentication", user.username);
+    let event = AuditEvent::new("totp_enabled", Some(&user.username), Some(client_ip), "");
+    if let Err(e) = state.audit_log_repo.record(&event).await {
+        error!("Failed to write audit log: {}", e);
+    }
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+/// Handler for the second login step, which exchanges a challenge
+/// token and a TOTP or recovery code for access and refresh tokens.
+pub async fn login_totp(
+    State(state): State<AppState>,
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+    jar: CookieJar,
+    Json(payload): Json<TotpLoginRequest>,
+) -> Result<Response> {
+    let claims = jwt::validate_challenge_token(&payload.challenge_token)
+        .map_err(|_| AuthError::InvalidToken)?;
+    let username = claims.sub;
+
+    // Codes are short, so guesses count as failed logins
+    throttle::check(&state, &username, client_ip).await?;
+
+    let credential = state
+        .totp_repo
+        .find(&username)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?
+        .filter(|credential| credential.enabled)
+        .ok_or(AuthError::TotpNotEnrolled)?;
+
+    let is_valid = match (&payload.code, &payload.recovery_code) {
+        (Some(code), _) => {
+            let step = totp::verify_code(
+                &credential.secret,
+                &username,
+                code,
+                credential.last_used_step,
+            )
+            .map_err(|_| AuthError::QueryFailure)?;
+
+            // Claiming the step can still fail if the same code was
+            // just used by a concurrent request
+            match step {
+                Some(step) => state
+                    .totp_repo
+                    .use_step(&username, step)
+                    .await
+                    .map_err(|_| AuthError::QueryFailure)?,
+                None => false,
+            }
+        }
+        (None, Some(recovery_code)) => {
+            let is_used = state
+                .totp_repo
+                .use_recovery_code(&username, &totp::hash_recovery_code(recovery_code))
+                .await
+                .map_err(|_| AuthError::QueryFailure)?;
+
+            if is_used {
+                info!("User {} logged in with a recovery code", username);
+                let event = AuditEvent::new("recovery_code_used", Some(&username), Some(client_ip), "");
+                if let Err(e) = state.audit_log_repo.record(&event).await {
+                    error!("Failed to write audit log: {}", e);
+                }
+            }
+
+            is_used
+        }
+        (None, None) => return Err(AuthError::MissingCredentials),
+    };
+
+    if !is_valid {
+        throttle::record_failure(&state, &username, client_ip).await?;
+        return Err(AuthError::InvalidTotpCode);
+    }
+
+    throttle::record_success(&state, &username).await?;
+
+    let user = state
+        .user_repo
+        .find(&username)
+        .await
+        .map_err(|_| AuthError::QueryFailure)?
+        .ok_or(AuthError::UserNotFound)?;
+
+    let client = SessionClient::new(&headers, client_ip);
+    let response = login_response(user, state, &client).await?;
+    Ok(token_response(&headers, jar, response))
+}
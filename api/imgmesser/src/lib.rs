@@ -0,0 +1,92 @@
+//! ImgMesser API
+//!
+//! Routes and middleware of the image API, which can be served on
+//! its own by the `imgmesser` binary or embedded in another service.
+
+pub mod maintenance;
+
+use axum::{
+    http::{header, method::Method},
+    routing::{delete, get, post},
+    Extension, Router,
+};
+use tower::ServiceBuilder;
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    trace::{DefaultMakeSpan, TraceLayer},
+};
+
+use auth::{client_ip::TrustedProxies, cookies};
+use config::ServerConfig;
+use handlers::{
+    change_password, current_user, delete_user, jwks, login, logout,
+    register, refresh,
+    enroll_totp, login_totp, verify_totp,
+    list_sessions, revoke_all_sessions, revoke_session,
+    delete_image, get_all_images_metadata, get_image,
+    get_image_metadata, get_image_thumbnail, process_image,
+    rename_image, restore_image_version, revert_image_version,
+    upload_images,
+};
+use state::AppState;
+
+/// Build the API router, served under the configured path prefix.
+///
+/// Handlers that log the client's address expect the router to
+/// be served with `into_make_service_with_connect_info`. Behind
+/// a configured trusted proxy, the address it forwards is used.
+pub fn build_router(state: AppState, config: &ServerConfig) -> Router {
+    // Configure CORS
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::exact(config.origin.clone()))
+        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::OPTIONS])
+        .allow_headers([
+            header::ACCEPT,
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::ORIGIN,
+            cookies::AUTH_MODE_HEADER,
+            cookies::CSRF_HEADER,
+        ])
+        .allow_credentials(true);
+
+    let routes = Router::new()
+        .route("/register", post(register))
+        .route("/login", post(login))
+        .route("/login/totp", post(login_totp))
+        .route("/logout", post(logout))
+        .route("/refresh", post(refresh))
+        .route("/user", get(current_user).delete(delete_user))
+        .route("/user/password", post(change_password))
+        .route("/user/sessions", get(list_sessions).delete(revoke_all_sessions))
+        .route("/user/sessions/{id}", delete(revoke_session))
+        .route("/user/totp", post(enroll_totp))
+        .route("/user/totp/verify", post(verify_totp))
+        .route("/.well-known/jwks.json", get(jwks))
+        .route("/images", get(get_all_images_metadata).post(upload_images))
+        .route("/images/{id}", get(get_image))
+        .route("/images/{id}/meta", get(get_image_metadata))
+        .route("/images/{id}/thumbnail", get(get_image_thumbnail))
+        .route("/images/{id}/delete", post(delete_image))
+        .route("/images/{id}/rename", post(rename_image))
+        .route("/images/{id}/revert", post(revert_image_version))
+        .route("/images/{id}/restore", post(restore_image_version))
+        .route("/images/{id}/transform", post(process_image))
+        .with_state(state);
+
+    let app = match config.path_prefix {
+        Some(ref prefix) => Router::new().nest(prefix, routes),
+        None => routes,
+    };
+
+    app
+        .layer(
+            ServiceBuilder::new()
+                .layer(cors)
+                .layer(Extension(TrustedProxies::new(config.trusted_proxies.clone())))
+        )
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(DefaultMakeSpan::default().include_headers(false)),
+        )
+}
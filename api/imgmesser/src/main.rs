@@ -1,19 +1,9 @@
 //! ImgMesser Server
 
 use anyhow::Result;
-use axum::{
-    http::{header, method::Method},
-    routing::{get, post},
-    Router,
-};
 use std::env;
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
-use tower::ServiceBuilder;
-use tower_http::{
-    cors::{AllowOrigin, CorsLayer},
-    trace::{DefaultMakeSpan, TraceLayer},
-};
 use tracing::info;
 use tracing_subscriber::{
     layer::SubscriberExt,
@@ -21,13 +11,7 @@ use tracing_subscriber::{
     EnvFilter,
 };
 
-use config;
-use handlers::{
-    current_user, login, logout, register, refresh,
-    delete_image, get_all_images_metadata, get_image,
-    get_image_metadata, rename_image, restore_image_version,
-    revert_image_version, upload_images,
-};
+use imgmesser::{build_router, maintenance};
 use state::AppState;
 
 #[tokio::main]
@@ -41,47 +25,18 @@ async fn main() -> Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    auth::keys::init_from_config().await?;
+    auth::validation::init_from_config().await?;
+
     let state = AppState::new().await?;
-    let addresses = config::get_addresses().await?;
+    let config = config::get_server_config().await?;
 
-    // Configure CORS
-    let cors = CorsLayer::new()
-        .allow_origin(AllowOrigin::exact(addresses.origin))
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-        .allow_headers([
-            header::ACCEPT,
-            header::AUTHORIZATION,
-            header::CONTENT_TYPE,
-            header::ORIGIN,
-        ])
-        .allow_credentials(true);
+    let purge_config = config::get_token_purge_config().await?;
+    maintenance::spawn_token_purge(state.clone(), purge_config);
 
-    let app = Router::new()
-        .route("/register", post(register))
-        .route("/login", post(login))
-        .route("/logout", post(logout))
-        .route("/refresh", post(refresh))
-        .route("/user", get(current_user))
-        .route("/images", get(get_all_images_metadata).post(upload_images))
-        .route("/images/{id}", get(get_image))
-        .route("/images/{id}/meta", get(get_image_metadata))
-        .route("/images/{id}/delete", post(delete_image))
-        .route("/images/{id}/rename", post(rename_image))
-        .route("/images/{id}/revert", post(revert_image_version))
-        .route("/images/{id}/restore", post(restore_image_version))
-        // TODO: add handlers, etc. for this route:
-        //.route("/images/{id}/transform", post(process_image))
-        .with_state(state)
-        .layer(
-            ServiceBuilder::new()
-                .layer(cors)
-        )
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(DefaultMakeSpan::default().include_headers(false)),
-        );
+    let app = build_router(state, &config);
 
-    let listener = TcpListener::bind(&addresses.listener).await?;
+    let listener = TcpListener::bind(&config.listener).await?;
     info!("Listening on {}...", &listener.local_addr()?);
 
     axum::serve(
@@ -0,0 +1,88 @@
+//! Background Maintenance
+//!
+//! Refresh tokens are only deleted when presented after expiring,
+//! and used tokens are kept to detect their reuse, so both are
+//! purged periodically in the background.
+
+use anyhow::Result;
+use chrono::{TimeDelta, Utc};
+use tokio::task::JoinHandle;
+use tokio::time::{self, MissedTickBehavior};
+use tracing::{error, info};
+
+use config::TokenPurgeConfig;
+use state::AppState;
+
+/// Delete expired tokens and tokens used longer ago than the
+/// retention window, a batch at a time, returning how many were
+/// deleted.
+pub async fn purge_refresh_tokens(state: &AppState, config: &TokenPurgeConfig) -> Result<u64> {
+    let used_before = Utc::now() - TimeDelta::from_std(config.retention)?;
+    let mut total = 0;
+
+    loop {
+        let purged = state
+            .refresh_token_repo
+            .purge_tokens(used_before, config.batch_size)
+            .await?;
+        total += purged;
+
+        if purged < config.batch_size as u64 {
+            return Ok(total);
+        }
+
+        // Let requests waiting on the database in between batches
+        tokio::task::yield_now().await;
+    }
+}
+
+/// Start purging refresh tokens on the configured interval, for as
+/// long as the server runs.
+pub fn spawn_token_purge(state: AppState, config: TokenPurgeConfig) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = time::interval(config.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            match purge_refresh_tokens(&state, &config).await {
+                Ok(0) => {}
+                Ok(purged) => info!("Purged {} refresh tokens", purged),
+                Err(e) => error!("Failed to purge refresh tokens: {:?}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_purge_deletes_used_tokens_in_batches() {
+        let state = AppState::builder().build().unwrap();
+        let repo = &state.refresh_token_repo;
+
+        for token in ["a", "b", "c"] {
+            repo.create_token("alice", token, None, None).await.unwrap();
+        }
+        repo.mark_token_used("a").await.unwrap();
+        repo.mark_token_used("b").await.unwrap();
+
+        let config = TokenPurgeConfig {
+            retention: Duration::ZERO,
+            batch_size: 1,
+            ..TokenPurgeConfig::default()
+        };
+        assert_eq!(purge_refresh_tokens(&state, &config).await.unwrap(), 2);
+
+        assert!(repo.find_by_token("a").await.unwrap().is_none());
+        assert!(repo.find_by_token("c").await.unwrap().is_some());
+
+        // Tokens used within the retention window are kept
+        repo.mark_token_used("c").await.unwrap();
+        assert_eq!(purge_refresh_tokens(&state, &TokenPurgeConfig::default()).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,982 @@
+//! End-to-end tests of the API routes, backed by in-memory
+//! repositories and object storage.
+
+use async_trait::async_trait;
+use axum::{
+    body::{Body, Bytes},
+    extract::connect_info::MockConnectInfo,
+    http::{header, Method, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use http_body_util::BodyExt;
+use image::{ImageFormat, RgbImage};
+use serde_json::{json, Value};
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use totp_rs::TOTP;
+use tower::ServiceExt;
+
+use config::ServerConfig;
+use imgmesser::build_router;
+use models::{User, UserInfo};
+use repos::{InMemoryUserRepo, UserRepoError, UserRepoOps};
+use state::AppState;
+use storage::{MemoryStore, ObjectStore};
+
+const BOUNDARY: &str = "imgmesser-test-boundary";
+
+fn server_config() -> ServerConfig {
+    ServerConfig::new("http://127.0.0.1:5173", "3000", false).unwrap()
+}
+
+fn app_with_state(state: AppState, config: &ServerConfig) -> Router {
+    build_router(state, config)
+        .layer(MockConnectInfo(SocketAddr::from(([127, 0, 0, 1], 4000))))
+}
+
+fn app_with_config(config: &ServerConfig) -> Router {
+    app_with_state(AppState::builder().build().unwrap(), config)
+}
+
+fn app() -> Router {
+    app_with_config(&server_config())
+}
+
+async fn send(app: &Router, request: Request<Body>) -> Response {
+    app.clone().oneshot(request).await.unwrap()
+}
+
+async fn body_bytes(response: Response) -> Bytes {
+    response.into_body().collect().await.unwrap().to_bytes()
+}
+
+async fn body_json(response: Response) -> Value {
+    serde_json::from_slice(&body_bytes(response).await).unwrap()
+}
+
+fn json_request(method: Method, uri: &str, token: Option<&str>, body: Value) -> Request<Body> {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json");
+
+    if let Some(token) = token {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+
+    builder.body(Body::from(body.to_string())).unwrap()
+}
+
+fn get_request(uri: &str, token: &str) -> Request<Body> {
+    Request::builder()
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let image = RgbImage::from_fn(width, height, |x, y| {
+        image::Rgb([(x * 8) as u8, (y * 8) as u8, 128])
+    });
+
+    let mut data = Vec::new();
+    image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png).unwrap();
+    data
+}
+
+fn upload_request(
+    token: Option<&str>,
+    username: Option<&str>,
+    file_name: &str,
+    data: &[u8],
+) -> Request<Body> {
+    let mut body = Vec::new();
+    if let Some(username) = username {
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\n\
+                Content-Disposition: form-data; name=\"user\"\r\n\r\n\
+                {username}\r\n",
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{BOUNDARY}\r\n\
+            Content-Disposition: form-data; name=\"files[]\"; filename=\"{file_name}\"\r\n\
+            Content-Type: image/png\r\n\r\n",
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+    let mut builder = Request::builder()
+        .method(Method::POST)
+        .uri("/images")
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        );
+
+    if let Some(token) = token {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+
+    builder.body(Body::from(body)).unwrap()
+}
+
+/// Register a user, log in, and return the login response.
+async fn register_and_login(app: &Router, username: &str) -> Value {
+    let credentials = json!({ "username": username, "password": "hunter22" });
+
+    let response = send(app, json_request(Method::POST, "/register", None, credentials.clone())).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(app, json_request(Method::POST, "/login", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    body_json(response).await
+}
+
+/// Upload an image for a user and return its id.
+async fn upload(app: &Router, username: &str, token: &str) -> String {
+    let request = upload_request(Some(token), Some(username), "photo.png", &png(32, 24));
+    let response = send(app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(app, get_request("/images", token)).await;
+    let list = body_json(response).await;
+    list["images"][0]["id"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_register_login_and_current_user() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+
+    let response = send(&app, get_request("/user", token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body_json(response).await["user"]["username"], "alice");
+}
+
+#[tokio::test]
+async fn test_register_rejects_taken_username() {
+    let app = app();
+    register_and_login(&app, "alice").await;
+
+    for username in ["alice", "Alice"] {
+        let credentials = json!({ "username": username, "password": "other password" });
+        let response = send(&app, json_request(Method::POST, "/register", None, credentials)).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+}
+
+/// User repository whose username check always passes, as when a
+/// concurrent registration takes the username right after it.
+#[derive(Default)]
+struct RacingUserRepo(InMemoryUserRepo);
+
+#[async_trait]
+impl UserRepoOps for RacingUserRepo {
+    async fn create(&self, user: &User) -> Result<UserInfo, UserRepoError> {
+        self.0.create(user).await
+    }
+
+    async fn authorize(&self, user: &User) -> Result<bool, UserRepoError> {
+        self.0.authorize(user).await
+    }
+
+    async fn find(&self, username: &str) -> Result<Option<UserInfo>, UserRepoError> {
+        self.0.find(username).await
+    }
+
+    async fn username_taken(&self, _username: &str) -> Result<bool, UserRepoError> {
+        Ok(false)
+    }
+
+    async fn update_password(&self, username: &str, password: &str) -> Result<(), UserRepoError> {
+        self.0.update_password(username, password).await
+    }
+
+    async fn delete(&self, username: &str) -> Result<(), UserRepoError> {
+        self.0.delete(username).await
+    }
+}
+
+#[tokio::test]
+async fn test_register_race_reports_taken_username() {
+    let state = AppState::builder()
+        .user_repo(Arc::new(RacingUserRepo::default()))
+        .build()
+        .unwrap();
+    let app = app_with_state(state, &server_config());
+    register_and_login(&app, "bob").await;
+
+    let credentials = json!({ "username": "Bob", "password": "other password" });
+    let response = send(&app, json_request(Method::POST, "/register", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_register_reports_invalid_fields() {
+    let app = app();
+
+    let credentials = json!({ "username": "root", "password": "short" });
+    let response = send(&app, json_request(Method::POST, "/register", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = body_json(response).await;
+    let fields: Vec<(&str, &str)> = body["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| (f["field"].as_str().unwrap(), f["code"].as_str().unwrap()))
+        .collect();
+    assert_eq!(fields, [("username", "reserved"), ("password", "too_short")]);
+
+    let credentials = json!({ "username": "", "password": "" });
+    let response = send(&app, json_request(Method::POST, "/register", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(body_json(response).await["error"], "Missing credentials");
+}
+
+#[tokio::test]
+async fn test_login_rejects_wrong_password() {
+    let app = app();
+    register_and_login(&app, "alice").await;
+
+    let credentials = json!({ "username": "alice", "password": "wrong" });
+    let response = send(&app, json_request(Method::POST, "/login", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_login_locks_out_account_after_repeated_failures() {
+    let state = AppState::builder().build().unwrap();
+    let app = app_with_state(state.clone(), &server_config());
+    register_and_login(&app, "alice").await;
+
+    // The sixth failure starts a lockout
+    for _ in 0..6 {
+        let credentials = json!({ "username": "alice", "password": "wrong" });
+        let response = send(&app, json_request(Method::POST, "/login", None, credentials)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let credentials = json!({ "username": "ALICE", "password": "hunter22" });
+    let response = send(&app, json_request(Method::POST, "/login", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::LOCKED);
+    assert_eq!(response.headers()[header::RETRY_AFTER], "15");
+
+    let events = state.audit_log_repo.find_by_username("alice", 10).await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event, "login_lockout");
+}
+
+#[tokio::test]
+async fn test_login_throttles_client_after_repeated_failures() {
+    let app = app();
+
+    for i in 0..21 {
+        let credentials = json!({ "username": format!("user{i}"), "password": "wrong" });
+        let response = send(&app, json_request(Method::POST, "/login", None, credentials)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let credentials = json!({ "username": "someone", "password": "wrong" });
+    let response = send(&app, json_request(Method::POST, "/login", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key(header::RETRY_AFTER));
+}
+
+#[tokio::test]
+async fn test_login_throttles_forwarded_client_behind_trusted_proxy() {
+    let state = AppState::builder().build().unwrap();
+    let config = server_config().with_trusted_proxies("127.0.0.0/8").unwrap();
+    let app = app_with_state(state.clone(), &config);
+
+    let login_from = |username: &str, forwarded_for: &'static str| {
+        let credentials = json!({ "username": username, "password": "wrong" });
+        let mut request = json_request(Method::POST, "/login", None, credentials);
+        request.headers_mut().insert("x-forwarded-for", forwarded_for.parse().unwrap());
+        request
+    };
+
+    for i in 0..21 {
+        let response = send(&app, login_from(&format!("user{i}"), "198.51.100.1")).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let response = send(&app, login_from("someone", "198.51.100.1")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // The client's lockout isn't in the history of the username it
+    // last tried
+    let events = state.audit_log_repo.find_by_username("user20", 10).await.unwrap();
+    assert!(events.is_empty());
+
+    // Other clients behind the proxy aren't throttled with it, and
+    // are audited by their own address
+    for _ in 0..6 {
+        let response = send(&app, login_from("someone", "198.51.100.2")).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let events = state.audit_log_repo.find_by_username("someone", 10).await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event, "login_lockout");
+    assert_eq!(events[0].client_ip.as_deref(), Some("198.51.100.2"));
+}
+
+#[tokio::test]
+async fn test_forwarded_headers_are_ignored_without_trusted_proxy() {
+    let app = app();
+
+    for i in 0..21 {
+        let credentials = json!({ "username": format!("user{i}"), "password": "wrong" });
+        let mut request = json_request(Method::POST, "/login", None, credentials);
+        request.headers_mut().insert("x-forwarded-for", format!("198.51.100.{i}").parse().unwrap());
+        assert_eq!(send(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let credentials = json!({ "username": "someone", "password": "wrong" });
+    let response = send(&app, json_request(Method::POST, "/login", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_refresh_rotates_tokens_and_detects_reuse() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let refresh_token = login["refresh_token"].as_str().unwrap();
+
+    let request = json!({ "refresh_token": refresh_token });
+    let response = send(&app, json_request(Method::POST, "/refresh", None, request.clone())).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let rotated = body_json(response).await["refresh_token"].clone();
+    assert_ne!(rotated.as_str().unwrap(), refresh_token);
+
+    // Reusing the old token revokes its session, but not others
+    let credentials = json!({ "username": "alice", "password": "hunter22" });
+    let other = body_json(send(&app, json_request(Method::POST, "/login", None, credentials)).await).await;
+
+    let response = send(&app, json_request(Method::POST, "/refresh", None, request)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let request = json!({ "refresh_token": rotated });
+    let response = send(&app, json_request(Method::POST, "/refresh", None, request)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let token = other["access_token"].as_str().unwrap();
+    let response = send(&app, get_request("/user/sessions", token)).await;
+    let body = body_json(response).await;
+    assert_eq!(body["sessions"].as_array().unwrap().len(), 1);
+    assert_eq!(body["security_events"][0]["event"], "refresh_token_reuse");
+
+    let request = json!({ "refresh_token": other["refresh_token"] });
+    let response = send(&app, json_request(Method::POST, "/refresh", None, request)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// Seconds since the epoch, for generating TOTP codes.
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[tokio::test]
+async fn test_totp_enrollment_and_two_step_login() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+
+    let response = send(&app, json_request(Method::POST, "/user/totp", Some(token), json!({}))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let uri = body_json(response).await["otpauth_uri"].as_str().unwrap().to_string();
+    let totp = TOTP::from_url(&uri).unwrap();
+
+    let request = json!({ "code": totp.generate(unix_time()) });
+    let response = send(&app, json_request(Method::POST, "/user/totp/verify", Some(token), request)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let recovery_codes = body_json(response).await["recovery_codes"].clone();
+    assert_eq!(recovery_codes.as_array().unwrap().len(), 10);
+
+    // The password alone now only gets a challenge
+    let credentials = json!({ "username": "alice", "password": "hunter22" });
+    let response = send(&app, json_request(Method::POST, "/login", None, credentials.clone())).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_json(response).await;
+    assert_eq!(body["mfa_required"], true);
+    assert!(body.get("access_token").is_none());
+    let challenge = body["challenge_token"].as_str().unwrap().to_string();
+
+    // Challenge tokens aren't access tokens
+    let response = send(&app, get_request("/user", &challenge)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // The enrollment code's step is used up, so use the next one
+    let code = totp.generate(unix_time() + 30);
+    let request = json!({ "challenge_token": challenge, "code": code });
+    let response = send(&app, json_request(Method::POST, "/login/totp", None, request.clone())).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body_json(response).await["access_token"].is_string());
+
+    let response = send(&app, json_request(Method::POST, "/login/totp", None, request)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Recovery codes work once each
+    let request = json!({ "challenge_token": challenge, "recovery_code": recovery_codes[0] });
+    let response = send(&app, json_request(Method::POST, "/login/totp", None, request.clone())).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(&app, json_request(Method::POST, "/login/totp", None, request)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_sessions_can_be_listed_and_revoked() {
+    let app = app();
+    let first = register_and_login(&app, "alice").await;
+    let token = first["access_token"].as_str().unwrap();
+
+    let credentials = json!({ "username": "alice", "password": "hunter22" });
+    let mut request = json_request(Method::POST, "/login", None, credentials);
+    request.headers_mut().insert(header::USER_AGENT, "Firefox".parse().unwrap());
+    let second = body_json(send(&app, request).await).await;
+
+    let response = send(&app, get_request("/user/sessions", token)).await;
+    let created_at = body_json(response).await["sessions"][0]["created_at"].clone();
+
+    // Refreshing keeps the session's client and start
+    let request = json!({ "refresh_token": second["refresh_token"] });
+    let response = send(&app, json_request(Method::POST, "/refresh", None, request)).await;
+    let rotated = body_json(response).await["refresh_token"].clone();
+
+    let response = send(&app, get_request("/user/sessions", token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let sessions = body_json(response).await["sessions"].as_array().unwrap().clone();
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0]["user_agent"], "Firefox");
+    assert_eq!(sessions[0]["client_ip"], "127.0.0.1");
+    assert_eq!(sessions[0]["created_at"], created_at);
+    assert!(sessions[0].get("token").is_none());
+
+    let uri = format!("/user/sessions/{}", sessions[0]["id"].as_str().unwrap());
+    let response = send(&app, json_request(Method::DELETE, &uri, Some(token), json!({}))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(&app, json_request(Method::DELETE, &uri, Some(token), json!({}))).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let request = json!({ "refresh_token": rotated });
+    let response = send(&app, json_request(Method::POST, "/refresh", None, request)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Other users' sessions can't be revoked
+    let other = register_and_login(&app, "bob").await;
+    let other_token = other["access_token"].as_str().unwrap();
+    let uri = format!("/user/sessions/{}", sessions[1]["id"].as_str().unwrap());
+    let response = send(&app, json_request(Method::DELETE, &uri, Some(other_token), json!({}))).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Logging out everywhere revokes the rest
+    let response = send(&app, json_request(Method::DELETE, "/user/sessions", Some(token), json!({}))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(&app, get_request("/user/sessions", token)).await;
+    assert!(body_json(response).await["sessions"].as_array().unwrap().is_empty());
+
+    let request = json!({ "refresh_token": first["refresh_token"] });
+    let response = send(&app, json_request(Method::POST, "/refresh", None, request)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_sessions_record_forwarded_client_behind_trusted_proxy() {
+    let config = server_config().with_trusted_proxies("127.0.0.1").unwrap();
+    let app = app_with_config(&config);
+    register_and_login(&app, "alice").await;
+
+    let credentials = json!({ "username": "alice", "password": "hunter22" });
+    let mut request = json_request(Method::POST, "/login", None, credentials);
+    request.headers_mut().insert("x-real-ip", "198.51.100.1".parse().unwrap());
+    let login = body_json(send(&app, request).await).await;
+    let token = login["access_token"].as_str().unwrap();
+
+    let response = send(&app, get_request("/user/sessions", token)).await;
+    let sessions = body_json(response).await["sessions"].as_array().unwrap().clone();
+    assert_eq!(sessions[0]["client_ip"], "198.51.100.1");
+    assert_eq!(sessions[1]["client_ip"], "127.0.0.1");
+}
+
+/// Cookies set by a response, as `name=value` pairs.
+fn set_cookies(response: &Response) -> Vec<String> {
+    response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap().split(';').next().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_cookie_auth_mode_requires_csrf_token() {
+    let app = app();
+    register_and_login(&app, "alice").await;
+
+    let credentials = json!({ "username": "alice", "password": "hunter22" });
+    let mut request = json_request(Method::POST, "/login", None, credentials);
+    request.headers_mut().insert("x-auth-mode", "cookie".parse().unwrap());
+    let response = send(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let set_cookie: Vec<_> = response.headers().get_all(header::SET_COOKIE).iter().collect();
+    assert_eq!(set_cookie.len(), 3);
+    for value in &set_cookie {
+        let value = value.to_str().unwrap();
+        assert!(value.contains("Secure") && value.contains("SameSite=Strict"));
+        assert_eq!(value.contains("HttpOnly"), !value.starts_with("csrf_token="));
+    }
+
+    let cookies = set_cookies(&response);
+    let cookie_header = cookies.join("; ");
+    let csrf_token = cookies
+        .iter()
+        .find_map(|cookie| cookie.strip_prefix("csrf_token="))
+        .unwrap()
+        .to_string();
+
+    // Tokens are only in cookies
+    let body = body_json(response).await;
+    assert_eq!(body["user"]["username"], "alice");
+    assert!(body.get("access_token").is_none());
+
+    let request = Request::builder()
+        .uri("/user")
+        .header(header::COOKIE, &cookie_header)
+        .body(Body::empty())
+        .unwrap();
+    let response = send(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let refresh_request = |csrf_token: Option<&str>| {
+        let mut builder = Request::builder()
+            .method(Method::POST)
+            .uri("/refresh")
+            .header(header::COOKIE, &cookie_header);
+
+        if let Some(csrf_token) = csrf_token {
+            builder = builder.header("x-csrf-token", csrf_token);
+        }
+
+        builder.body(Body::empty()).unwrap()
+    };
+
+    let response = send(&app, refresh_request(None)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = send(&app, refresh_request(Some("wrong"))).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = send(&app, refresh_request(Some(&csrf_token))).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let cookies = set_cookies(&response);
+    assert_eq!(cookies.len(), 3);
+
+    // Logging out needs the new CSRF token, and clears the cookies
+    let cookie_header = cookies.join("; ");
+    let csrf_token = cookies
+        .iter()
+        .find_map(|cookie| cookie.strip_prefix("csrf_token="))
+        .unwrap();
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/logout")
+        .header(header::COOKIE, &cookie_header)
+        .header("x-csrf-token", csrf_token)
+        .body(Body::empty())
+        .unwrap();
+    let response = send(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(set_cookies(&response).iter().all(|cookie| cookie.ends_with('=')));
+}
+
+#[tokio::test]
+async fn test_images_require_auth() {
+    let app = app();
+    let request = Request::builder().uri("/images").body(Body::empty()).unwrap();
+
+    let response = send(&app, request).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_upload_without_user_field_uses_token_owner() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+
+    let request = upload_request(Some(token), None, "photo.png", &png(8, 8));
+    let response = send(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(&app, get_request("/images", token)).await;
+    assert_eq!(body_json(response).await["total"], 1);
+}
+
+#[tokio::test]
+async fn test_upload_requires_auth() {
+    let app = app();
+    register_and_login(&app, "alice").await;
+
+    let request = upload_request(None, Some("alice"), "photo.png", &png(8, 8));
+    let response = send(&app, request).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let request = upload_request(Some("not-a-jwt"), Some("alice"), "photo.png", &png(8, 8));
+    let response = send(&app, request).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_upload_rejects_mismatched_user() {
+    let app = app();
+    let alice = register_and_login(&app, "alice").await;
+    let mallory = register_and_login(&app, "mallory").await;
+    let token = mallory["access_token"].as_str().unwrap();
+
+    let request = upload_request(Some(token), Some("alice"), "photo.png", &png(8, 8));
+    let response = send(&app, request).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Nothing was stored for either user
+    for login in [&alice, &mallory] {
+        let token = login["access_token"].as_str().unwrap();
+        let response = send(&app, get_request("/images", token)).await;
+        assert_eq!(body_json(response).await["total"], 0);
+    }
+}
+
+#[tokio::test]
+async fn test_upload_and_get_image() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+    let image_id = upload(&app, "alice", token).await;
+
+    let response = send(&app, get_request(&format!("/images/{image_id}"), token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+    assert_eq!(body_bytes(response).await, png(32, 24));
+
+    let response = send(&app, get_request(&format!("/images/{image_id}/meta"), token)).await;
+    let meta = body_json(response).await;
+    assert_eq!(meta["name"], "photo.png");
+    assert_eq!(meta["width"], 32);
+    assert_eq!(meta["height"], 24);
+}
+
+#[tokio::test]
+async fn test_images_are_private_to_their_owner() {
+    let app = app();
+    let alice = register_and_login(&app, "alice").await;
+    let image_id = upload(&app, "alice", alice["access_token"].as_str().unwrap()).await;
+
+    let bob = register_and_login(&app, "bob").await;
+    let token = bob["access_token"].as_str().unwrap();
+
+    let response = send(&app, get_request(&format!("/images/{image_id}"), token)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send(&app, get_request("/images", token)).await;
+    assert_eq!(body_json(response).await["total"], 0);
+}
+
+#[tokio::test]
+async fn test_transform_revert_and_restore() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+    let image_id = upload(&app, "alice", token).await;
+
+    let operations = json!({ "operations": [{ "resize": { "width": 16 } }] });
+    let uri = format!("/images/{image_id}/transform");
+    let response = send(&app, json_request(Method::POST, &uri, Some(token), operations)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let meta_uri = format!("/images/{image_id}/meta");
+    let meta = body_json(send(&app, get_request(&meta_uri, token)).await).await;
+    assert_eq!(meta["width"], 16);
+    assert_eq!(meta["version_count"], 2);
+
+    let uri = format!("/images/{image_id}/revert");
+    let response = send(&app, json_request(Method::POST, &uri, Some(token), json!({}))).await;
+    assert_eq!(body_json(response).await["updated"], true);
+
+    let meta = body_json(send(&app, get_request(&meta_uri, token)).await).await;
+    assert_eq!(meta["width"], 32);
+    assert_eq!(meta["initial_version"], true);
+
+    let uri = format!("/images/{image_id}/restore");
+    let response = send(&app, json_request(Method::POST, &uri, Some(token), json!({}))).await;
+    assert_eq!(body_json(response).await["updated"], true);
+
+    let meta = body_json(send(&app, get_request(&meta_uri, token)).await).await;
+    assert_eq!(meta["width"], 16);
+}
+
+#[tokio::test]
+async fn test_transform_keeps_name_when_format_is_unchanged() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+
+    let request = upload_request(Some(token), Some("alice"), "photo.PNG", &png(32, 24));
+    assert_eq!(send(&app, request).await.status(), StatusCode::OK);
+    let list = body_json(send(&app, get_request("/images", token)).await).await;
+    let image_id = list["images"][0]["id"].as_str().unwrap();
+
+    let operations = json!({ "operations": [{ "resize": { "width": 16 } }] });
+    let uri = format!("/images/{image_id}/transform");
+    let response = send(&app, json_request(Method::POST, &uri, Some(token), operations)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let meta_uri = format!("/images/{image_id}/meta");
+    let meta = body_json(send(&app, get_request(&meta_uri, token)).await).await;
+    assert_eq!(meta["name"], "photo.PNG");
+}
+
+#[tokio::test]
+async fn test_get_image_negotiates_format() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+    let image_id = upload(&app, "alice", token).await;
+
+    let request = Request::builder()
+        .uri(format!("/images/{image_id}"))
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::ACCEPT, "image/webp,*/*;q=0.8")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = send(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "image/webp");
+    assert_eq!(response.headers()[header::VARY], "Accept");
+}
+
+#[tokio::test]
+async fn test_delete_image() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+    let image_id = upload(&app, "alice", token).await;
+
+    let uri = format!("/images/{image_id}/delete");
+    let response = send(&app, json_request(Method::POST, &uri, Some(token), json!({}))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(&app, get_request("/images", token)).await;
+    assert_eq!(body_json(response).await["total"], 0);
+}
+
+#[tokio::test]
+async fn test_delete_image_removes_cached_thumbnails_and_renditions() {
+    let store = Arc::new(MemoryStore::new());
+    let state = AppState::builder().object_store(store.clone()).build().unwrap();
+    let app = app_with_state(state, &server_config());
+
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+    let image_id = upload(&app, "alice", token).await;
+
+    let response = send(&app, get_request(&format!("/images/{image_id}?w=8"), token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let keys = || async {
+        let objects = store.list("").await.unwrap();
+        objects.into_iter().map(|object| object.key).collect::<Vec<String>>()
+    };
+    let cached = keys().await;
+    assert!(cached.iter().any(|key| key.starts_with("thumbnails/")));
+    assert!(cached.iter().any(|key| key.starts_with("renditions/")));
+
+    let uri = format!("/images/{image_id}/delete");
+    let response = send(&app, json_request(Method::POST, &uri, Some(token), json!({}))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(keys().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_image_list_pages_past_the_end_are_empty() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+    upload(&app, "alice", token).await;
+
+    let response = send(&app, get_request("/images?page=5&limit=10", token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let list = body_json(response).await;
+    assert_eq!(list["images"], json!([]));
+    assert_eq!(list["total"], 1);
+    assert_eq!(list["has_more"], false);
+}
+
+#[tokio::test]
+async fn test_change_password_revokes_refresh_tokens() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+
+    let request = json!({ "current_password": "wrong", "new_password": "hunter23" });
+    let response = send(&app, json_request(Method::POST, "/user/password", Some(token), request)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let request = json!({ "current_password": "hunter22", "new_password": "hunter23" });
+    let response = send(&app, json_request(Method::POST, "/user/password", Some(token), request)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = json!({ "refresh_token": login["refresh_token"] });
+    let response = send(&app, json_request(Method::POST, "/refresh", None, request)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let credentials = json!({ "username": "alice", "password": "hunter22" });
+    let response = send(&app, json_request(Method::POST, "/login", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let credentials = json!({ "username": "alice", "password": "hunter23" });
+    let response = send(&app, json_request(Method::POST, "/login", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_password_confirmation_is_throttled() {
+    let app = app();
+    let login = register_and_login(&app, "alice").await;
+    let token = login["access_token"].as_str().unwrap();
+
+    // Guesses through either route count towards the same lockout
+    for i in 0..6 {
+        let response = if i % 2 == 0 {
+            let request = json!({ "current_password": "wrong", "new_password": "hunter23" });
+            send(&app, json_request(Method::POST, "/user/password", Some(token), request)).await
+        } else {
+            let request = json!({ "password": "wrong" });
+            send(&app, json_request(Method::DELETE, "/user", Some(token), request)).await
+        };
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let request = json!({ "current_password": "hunter22", "new_password": "hunter23" });
+    let response = send(&app, json_request(Method::POST, "/user/password", Some(token), request)).await;
+    assert_eq!(response.status(), StatusCode::LOCKED);
+
+    let request = json!({ "password": "hunter22" });
+    let response = send(&app, json_request(Method::DELETE, "/user", Some(token), request)).await;
+    assert_eq!(response.status(), StatusCode::LOCKED);
+
+    let credentials = json!({ "username": "alice", "password": "hunter22" });
+    let response = send(&app, json_request(Method::POST, "/login", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::LOCKED);
+}
+
+#[tokio::test]
+async fn test_delete_user_removes_images_and_objects() {
+    let store = Arc::new(MemoryStore::new());
+    let state = AppState::builder().object_store(store.clone()).build().unwrap();
+    let app = app_with_state(state, &server_config());
+
+    let alice = register_and_login(&app, "alice").await;
+    let alice_token = alice["access_token"].as_str().unwrap();
+    let image_id = upload(&app, "alice", alice_token).await;
+
+    let operations = json!({ "operations": [{ "resize": { "width": 16 } }] });
+    let uri = format!("/images/{image_id}/transform");
+    let response = send(&app, json_request(Method::POST, &uri, Some(alice_token), operations)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let bob = register_and_login(&app, "bob").await;
+    let bob_token = bob["access_token"].as_str().unwrap();
+    upload(&app, "bob", bob_token).await;
+
+    let base_path = alice["user"]["object_base_path"].as_str().unwrap();
+    let keys = |objects: Vec<storage::ObjectInfo>| -> Vec<String> {
+        objects.into_iter().map(|object| object.key).collect()
+    };
+    let bob_keys: Vec<String> = keys(store.list("").await.unwrap())
+        .into_iter()
+        .filter(|key| !key.contains(base_path))
+        .collect();
+
+    let request = json!({ "password": "wrong" });
+    let response = send(&app, json_request(Method::DELETE, "/user", Some(alice_token), request)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let request = json!({ "password": "hunter22" });
+    let response = send(&app, json_request(Method::DELETE, "/user", Some(alice_token), request)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Only bob's objects are left
+    assert_eq!(keys(store.list("").await.unwrap()), bob_keys);
+
+    let response = send(&app, get_request("/user", alice_token)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let credentials = json!({ "username": "alice", "password": "hunter22" });
+    let response = send(&app, json_request(Method::POST, "/login", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_routes_are_served_under_path_prefix() {
+    let app = app_with_config(&server_config().with_path_prefix("api/"));
+
+    let credentials = json!({ "username": "alice", "password": "hunter22" });
+    let response = send(&app, json_request(Method::POST, "/api/register", None, credentials.clone())).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = send(&app, json_request(Method::POST, "/register", None, credentials)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_cors_allows_configured_origin() {
+    let app = app();
+    let request = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/images")
+        .header(header::ORIGIN, "http://127.0.0.1:5173")
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = send(&app, request).await;
+    assert_eq!(
+        response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "http://127.0.0.1:5173",
+    );
+}
+
+#[tokio::test]
+async fn test_jwks_is_published() {
+    let app = app();
+    let request = Request::builder()
+        .uri("/.well-known/jwks.json")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = send(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body_json(response).await["keys"].is_array());
+}
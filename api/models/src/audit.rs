@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/// A security-relevant event, such as an account lockout.
+#[derive(Clone, Serialize, Deserialize, FromRow)]
+pub struct AuditEvent {
+    pub id: Uuid,
+    pub event: String,
+    pub username: Option<String>,
+    pub client_ip: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEvent {
+    pub fn new(
+        event: &str,
+        username: Option<&str>,
+        client_ip: Option<IpAddr>,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            id: Uuid::now_v7(),
+            event: event.to_string(),
+            username: username.map(String::from),
+            client_ip: client_ip.map(|ip| ip.to_string()),
+            detail: Some(detail.into()),
+            created_at: Utc::now(),
+        }
+    }
+}
@@ -3,6 +3,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{
     postgres::PgRow,
+    types::Json,
     Error, FromRow, Row,
 };
 use std::{
@@ -11,6 +12,8 @@ use std::{
 };
 use uuid::Uuid;
 
+use super::transform::Operation;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 /// Image database values
 pub struct Image {
@@ -27,6 +30,14 @@ pub struct Image {
     pub version_index: i64,
     pub latest_version: bool,
     pub initial_version: bool,
+
+    /// Operations that produced the current version from
+    /// the one before it; empty for uploaded versions
+    pub operations: Vec<Operation>,
+
+    /// S3 object key, if stored
+    #[serde(skip)]
+    pub object_key: Option<String>,
 }
 
 impl<'a> FromRow<'a, PgRow> for Image {
@@ -35,6 +46,7 @@ impl<'a> FromRow<'a, PgRow> for Image {
         let content_type = ContentType::from_int(content_type_int);
         let created_at: DateTime<Utc> = row.try_get("created_at")?;
         let last_modified: DateTime<Utc> = row.try_get("last_modified")?;
+        let operations: Option<Json<Vec<Operation>>> = row.try_get("operations")?;
 
         let image = Image {
             id: row.try_get("id")?,
@@ -50,6 +62,8 @@ impl<'a> FromRow<'a, PgRow> for Image {
             version_index: row.try_get("version_index")?,
             latest_version: row.try_get("latest_version")?,
             initial_version: row.try_get("initial_version")?,
+            operations: operations.map(|ops| ops.0).unwrap_or_default(),
+            object_key: row.try_get("object_key")?,
         };
 
         Ok(image)
@@ -63,6 +77,7 @@ pub struct ImageInfo {
     pub username: String,
     pub content_type: i32,
     pub version: String,
+    pub object_key: Option<String>,
 }
 
 #[derive(Clone, Debug, FromRow)]
@@ -77,10 +92,15 @@ pub struct UploadImage {
     pub content_type: ContentType,
     pub data: Bytes,
     pub dimensions: (u32, u32),
+
+    /// Operations applied to produce this image from
+    /// the current version, if it's an edit
+    pub operations: Option<Vec<Operation>>,
 }
 
 /// Image bytes and content type
 pub struct ImageData {
+    pub name: String,
     pub content_type: String,
     pub data: Bytes,
 }
@@ -92,7 +112,7 @@ pub struct ImageList {
     pub has_more: bool,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ContentType {
     UNKNOWN,
     JPEG,
@@ -100,9 +120,14 @@ pub enum ContentType {
     GIF,
     WEBP,
     BMP,
+
+    /// AVIF can be encoded but not decoded, so it's only used for
+    /// transcoded renditions, never for uploaded images
+    AVIF,
 }
 
 impl ContentType {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(content_type: &str) -> Self {
         match content_type.strip_prefix("image/").unwrap_or(content_type) {
             "jpg" | "jpeg" => ContentType::JPEG,
@@ -121,6 +146,19 @@ impl ContentType {
             ContentType::UNKNOWN
         }
     }
+
+    /// File extension conventionally used for this content type.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ContentType::JPEG => "jpg",
+            ContentType::PNG => "png",
+            ContentType::GIF => "gif",
+            ContentType::WEBP => "webp",
+            ContentType::BMP => "bmp",
+            ContentType::AVIF => "avif",
+            ContentType::UNKNOWN => "bin",
+        }
+    }
 }
 
 impl TryFrom<i32> for ContentType {
@@ -133,6 +171,7 @@ impl TryFrom<i32> for ContentType {
             x if x == ContentType::GIF as i32 => Ok(ContentType::GIF),
             x if x == ContentType::WEBP as i32 => Ok(ContentType::WEBP),
             x if x == ContentType::BMP as i32 => Ok(ContentType::BMP),
+            x if x == ContentType::AVIF as i32 => Ok(ContentType::AVIF),
             x if x == ContentType::UNKNOWN as i32 => Ok(ContentType::UNKNOWN),
             _ => Err(()),
         }
@@ -147,6 +186,7 @@ impl fmt::Display for ContentType {
             ContentType::GIF => write!(f, "image/gif"),
             ContentType::WEBP => write!(f, "image/webp"),
             ContentType::BMP => write!(f, "image/bmp"),
+            ContentType::AVIF => write!(f, "image/avif"),
             ContentType::UNKNOWN => write!(f, "application/octet-stream"),
         }
     }
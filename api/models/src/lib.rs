@@ -1,10 +1,22 @@
+mod audit;
 mod image;
+mod login_attempt;
 mod refresh_token;
+mod totp;
+mod transform;
 mod user;
 
+pub use audit::AuditEvent;
 pub use image::{
     ContentType, Image, ImageData, ImageInfo,
     ImageList, ImageVersion, UploadImage,
 };
-pub use refresh_token::RefreshToken;
+pub use login_attempt::LoginAttempts;
+pub use refresh_token::{RefreshToken, Session};
+pub use totp::TotpCredential;
+pub use transform::{
+    BlurOptions, Color, ConvertOptions, CropOptions, FlipDirection,
+    KernelOptions, Operation, OutputFormat, ResizeFilter, ResizeFit,
+    ResizeOptions, RotateOptions, UnsharpenOptions,
+};
 pub use user::{User, UserInfo};
@@ -0,0 +1,21 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Failed login attempts from one client IP or against one username.
+#[derive(Clone, Serialize, Deserialize, FromRow)]
+pub struct LoginAttempts {
+    pub key: String,
+    pub failures: i32,
+    pub last_failure_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+impl LoginAttempts {
+    /// Time left until the lockout ends, if locked out.
+    pub fn locked_for(&self) -> Option<TimeDelta> {
+        self.locked_until
+            .map(|until| until - Utc::now())
+            .filter(|remaining| *remaining > TimeDelta::zero())
+    }
+}
@@ -7,12 +7,27 @@ use uuid::Uuid;
 pub struct RefreshToken {
     pub id: Uuid,
     pub username: String,
-    pub token: String,
+
+    /// SHA-256 hash of the token, which itself isn't stored
+    pub token_hash: String,
     pub expires_at: DateTime<Utc>,
     pub is_used: bool,
     pub used_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub last_used_at: DateTime<Utc>,
+
+    /// Id of the first token of the chain of rotations this token
+    /// belongs to
+    pub family_id: Uuid,
+
+    /// User agent of the client that logged in
+    pub user_agent: Option<String>,
+
+    /// IP address of the client that logged in
+    pub client_ip: Option<String>,
+
+    /// When the first token of the family was made
+    pub session_created_at: DateTime<Utc>,
 }
 
 impl RefreshToken {
@@ -24,3 +39,29 @@ impl RefreshToken {
         !self.is_expired() && !self.is_used
     }
 }
+
+/// An active login of a user, as shown to them. Sessions are
+/// identified by their refresh token family, which stays the same
+/// across rotations.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub client_ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl From<RefreshToken> for Session {
+    fn from(token: RefreshToken) -> Self {
+        Self {
+            id: token.family_id,
+            user_agent: token.user_agent,
+            client_ip: token.client_ip,
+            created_at: token.session_created_at,
+            last_used_at: token.last_used_at,
+            expires_at: token.expires_at,
+        }
+    }
+}
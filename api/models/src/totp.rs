@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A user's TOTP secret, which only takes effect once enabled by
+/// verifying a first code.
+#[derive(Clone, Serialize, Deserialize, FromRow)]
+pub struct TotpCredential {
+    pub username: String,
+
+    /// Base32-encoded shared secret
+    pub secret: String,
+    pub enabled: bool,
+
+    /// Last time step a code was accepted for, so codes can't be
+    /// replayed
+    pub last_used_step: Option<i64>,
+}
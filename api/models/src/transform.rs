@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+
+use super::image::ContentType;
+
+/// A single image editing operation
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Resize(ResizeOptions),
+    Crop(CropOptions),
+    Rotate(RotateOptions),
+    Flip(FlipDirection),
+    Blur(BlurOptions),
+    Unsharpen(UnsharpenOptions),
+
+    /// Sobel edge detection
+    EdgeDetect,
+
+    Convolve(KernelOptions),
+
+    /// Amount added to each color channel, from -255 to 255
+    Brightness(i32),
+
+    /// Percentage change in contrast, from -100 to 100
+    Contrast(f32),
+
+    /// Hue rotation in degrees, from -360 to 360
+    HueRotate(i32),
+
+    /// Percentage change in saturation, from -100 (grayscale) to 100
+    Saturation(f32),
+
+    Grayscale,
+    Invert,
+
+    /// Re-encode the result in another format
+    Convert(ConvertOptions),
+}
+
+/// Options for resizing an image
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ResizeOptions {
+    /// Target width in pixels; derived from the aspect ratio if omitted
+    pub width: Option<u32>,
+
+    /// Target height in pixels; derived from the aspect ratio if omitted
+    pub height: Option<u32>,
+
+    #[serde(default)]
+    pub fit: ResizeFit,
+
+    #[serde(default)]
+    pub filter: ResizeFilter,
+}
+
+/// How a resized image fits within the target dimensions
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeFit {
+    /// Scale to fit within the target, preserving aspect ratio
+    #[default]
+    Contain,
+
+    /// Scale to fill the target, preserving aspect ratio and
+    /// cropping any overflow
+    Cover,
+
+    /// Scale to exactly the target, ignoring aspect ratio
+    Exact,
+}
+
+/// Sampling filter used when resizing
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    #[default]
+    Lanczos3,
+}
+
+/// Rectangle of an image to crop to
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CropOptions {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Options for rotating an image
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RotateOptions {
+    /// Clockwise rotation in degrees
+    pub angle: f32,
+
+    /// Fill for the corners exposed by a non-right-angle rotation
+    #[serde(default)]
+    pub background: Color,
+}
+
+/// Axis along which an image is mirrored
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FlipDirection {
+    Horizontal,
+    Vertical,
+}
+
+/// Options for blurring an image
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BlurOptions {
+    /// Standard deviation of the Gaussian kernel
+    pub sigma: f32,
+}
+
+/// Options for sharpening an image with an unsharp mask
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct UnsharpenOptions {
+    /// Standard deviation of the Gaussian blur used for the mask
+    pub sigma: f32,
+
+    /// Minimum brightness difference, from 0 to 255, for a pixel
+    /// to be sharpened
+    #[serde(default)]
+    pub threshold: i32,
+}
+
+/// A user-supplied convolution kernel
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct KernelOptions {
+    /// Row-major weights of a 3x3 or 5x5 kernel
+    pub kernel: Vec<f32>,
+
+    /// Value each weight is divided by; defaults to the sum of
+    /// the weights, or 1 if they sum to zero
+    pub divisor: Option<f32>,
+}
+
+/// Options for converting an image to another format
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ConvertOptions {
+    pub format: OutputFormat,
+
+    /// JPEG or AVIF quality, from 1 to 100
+    pub quality: Option<u8>,
+
+    /// PNG compression level, from 0 (none) to 9 (best)
+    pub compression: Option<u8>,
+}
+
+/// Image format an image can be converted to
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    Gif,
+    Webp,
+    Bmp,
+
+    /// Only supported for renditions and previews, since AVIF
+    /// images can't be decoded for further editing
+    Avif,
+}
+
+impl From<OutputFormat> for ContentType {
+    fn from(format: OutputFormat) -> Self {
+        match format {
+            OutputFormat::Jpeg => ContentType::JPEG,
+            OutputFormat::Png => ContentType::PNG,
+            OutputFormat::Gif => ContentType::GIF,
+            OutputFormat::Webp => ContentType::WEBP,
+            OutputFormat::Bmp => ContentType::BMP,
+            OutputFormat::Avif => ContentType::AVIF,
+        }
+    }
+}
+
+/// RGBA color, written as a `#rrggbb` or `#rrggbbaa` hex string
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub [u8; 4]);
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let hex = value.strip_prefix('#').unwrap_or(&value);
+        if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+            return Err(format!("invalid color: {}", value));
+        }
+
+        let mut rgba = [0, 0, 0, 255];
+        for (i, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+            *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+                .map_err(|_| format!("invalid color: {}", value))?;
+        }
+
+        Ok(Color(rgba))
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        let [r, g, b, a] = color.0;
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_from_rgb_hex() {
+        let color = Color::try_from("#ff8000".to_string()).unwrap();
+        assert_eq!(color, Color([255, 128, 0, 255]));
+    }
+
+    #[test]
+    fn test_color_from_rgba_hex() {
+        let color = Color::try_from("00000080".to_string()).unwrap();
+        assert_eq!(color, Color([0, 0, 0, 128]));
+    }
+
+    #[test]
+    fn test_color_from_invalid_hex() {
+        assert!(Color::try_from("#fff".to_string()).is_err());
+    }
+}
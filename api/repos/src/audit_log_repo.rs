@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use sqlx::{Error as SqlxError, PgPool};
+
+use models::AuditEvent;
+
+#[derive(Clone)]
+pub struct AuditLogRepo {
+    db: PgPool,
+}
+
+impl AuditLogRepo {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+pub trait AuditLogRepoOps: Send + Sync {
+    async fn record(&self, event: &AuditEvent) -> Result<(), SqlxError>;
+
+    /// Get a user's most recent events, newest first.
+    async fn find_by_username(
+        &self,
+        username: &str,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>, SqlxError>;
+}
+
+#[async_trait]
+impl AuditLogRepoOps for AuditLogRepo {
+    async fn record(&self, event: &AuditEvent) -> Result<(), SqlxError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_log (id, event, username, client_ip, detail, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            event.id,
+            event.event,
+            event.username,
+            event.client_ip,
+            event.detail,
+            event.created_at,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_by_username(
+        &self,
+        username: &str,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>, SqlxError> {
+        let events = sqlx::query_as::<_, AuditEvent>(
+            r#"
+            SELECT id, event, username, client_ip, detail, created_at
+            FROM audit_log WHERE username = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(username)
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(events)
+    }
+}
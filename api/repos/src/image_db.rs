@@ -0,0 +1,159 @@
+//! Image Metadata Queries
+//!
+//! The queries the image repository makes, so that tests can keep
+//! image metadata in memory while the repository's own object
+//! handling runs unchanged.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use models::{ContentType, Image, ImageInfo, Operation};
+
+#[async_trait]
+pub(crate) trait ImageDb: Send + Sync {
+    async fn insert_image(
+        &self,
+        id: &Uuid,
+        name: &str,
+        content_type: ContentType,
+        username: &str,
+        object_key: &str,
+    ) -> Result<()>;
+
+    async fn insert_image_version(
+        &self,
+        image_id: &Uuid,
+        version: &str,
+        dimensions: (u32, u32),
+        size: usize,
+        content_type: ContentType,
+        operations: Option<&[Operation]>,
+    ) -> Result<()>;
+
+    async fn find_image(&self, id: &Uuid, username: &str) -> Result<Option<ImageInfo>>;
+
+    async fn find_image_with_version_info(
+        &self,
+        id: &Uuid,
+        username: &str,
+    ) -> Result<Option<Image>>;
+
+    async fn find_image_id_by_name(&self, name: &str, username: &str) -> Result<Option<Uuid>>;
+
+    async fn find_all_images(&self, username: &str) -> Result<Vec<Image>>;
+
+    async fn delete_image(&self, image_id: &Uuid) -> Result<()>;
+
+    async fn delete_all_images(&self, username: &str) -> Result<()>;
+
+    async fn revert_image_version(&self, image_id: &Uuid) -> Result<Option<String>>;
+
+    async fn restore_image_version(&self, image_id: &Uuid) -> Result<Option<String>>;
+
+    async fn rename_image(
+        &self,
+        image_id: &Uuid,
+        new_name: &str,
+        object_key: &str,
+    ) -> Result<Option<String>>;
+
+    async fn update_image_format(
+        &self,
+        image_id: &Uuid,
+        content_type: ContentType,
+        new_name: &str,
+        object_key: &str,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+impl ImageDb for PgPool {
+    async fn insert_image(
+        &self,
+        id: &Uuid,
+        name: &str,
+        content_type: ContentType,
+        username: &str,
+        object_key: &str,
+    ) -> Result<()> {
+        db::insert_image(self, id, name, content_type, username, object_key).await
+    }
+
+    async fn insert_image_version(
+        &self,
+        image_id: &Uuid,
+        version: &str,
+        dimensions: (u32, u32),
+        size: usize,
+        content_type: ContentType,
+        operations: Option<&[Operation]>,
+    ) -> Result<()> {
+        db::insert_image_version(
+            self,
+            image_id,
+            version,
+            dimensions,
+            size,
+            content_type,
+            operations,
+        )
+        .await
+    }
+
+    async fn find_image(&self, id: &Uuid, username: &str) -> Result<Option<ImageInfo>> {
+        db::find_image(self, id, username).await
+    }
+
+    async fn find_image_with_version_info(
+        &self,
+        id: &Uuid,
+        username: &str,
+    ) -> Result<Option<Image>> {
+        db::find_image_with_version_info(self, id, username).await
+    }
+
+    async fn find_image_id_by_name(&self, name: &str, username: &str) -> Result<Option<Uuid>> {
+        db::find_image_id_by_name(self, name, username).await
+    }
+
+    async fn find_all_images(&self, username: &str) -> Result<Vec<Image>> {
+        db::find_all_images(self, username).await
+    }
+
+    async fn delete_image(&self, image_id: &Uuid) -> Result<()> {
+        db::delete_image(self, image_id).await
+    }
+
+    async fn delete_all_images(&self, username: &str) -> Result<()> {
+        db::delete_all_images(self, username).await
+    }
+
+    async fn revert_image_version(&self, image_id: &Uuid) -> Result<Option<String>> {
+        db::revert_image_version(self, image_id).await
+    }
+
+    async fn restore_image_version(&self, image_id: &Uuid) -> Result<Option<String>> {
+        db::restore_image_version(self, image_id).await
+    }
+
+    async fn rename_image(
+        &self,
+        image_id: &Uuid,
+        new_name: &str,
+        object_key: &str,
+    ) -> Result<Option<String>> {
+        db::rename_image(self, image_id, new_name, object_key).await
+    }
+
+    async fn update_image_format(
+        &self,
+        image_id: &Uuid,
+        content_type: ContentType,
+        new_name: &str,
+        object_key: &str,
+    ) -> Result<()> {
+        db::update_image_format(self, image_id, content_type, new_name, object_key).await
+    }
+}
@@ -1,30 +1,38 @@
 use async_trait::async_trait;
-use aws_sdk_s3::Client as S3Client;
+use bytes::Bytes;
 use sqlx::PgPool;
 use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
 use tracing::error;
 use uuid::Uuid;
 
-use db;
 use errors::ImageError;
 use models::{
-    ContentType, Image, ImageData, ImageInfo, ImageList,
-    UploadImage, UserInfo,
+    ContentType, ConvertOptions, Image, ImageData, ImageInfo, ImageList,
+    Operation, OutputFormat, ResizeOptions, UploadImage, UserInfo,
 };
-use s3;
+use storage::ObjectStore;
+
+use crate::image_db::ImageDb;
 
 type Result<T> = anyhow::Result<T, ImageError>;
 
 #[derive(Clone)]
 pub struct ImageRepo {
-    db: PgPool,
-    img_store_client: S3Client,
+    db: Arc<dyn ImageDb>,
+    object_store: Arc<dyn ObjectStore>,
 }
 
 impl ImageRepo {
-    pub fn new(db: PgPool, img_store_client: S3Client) -> Self {
-        Self { db, img_store_client }
+    pub fn new(db: PgPool, object_store: Arc<dyn ObjectStore>) -> Self {
+        Self::with_db(Arc::new(db), object_store)
+    }
+
+    /// Make a repository whose metadata queries are answered by
+    /// the given database.
+    pub(crate) fn with_db(db: Arc<dyn ImageDb>, object_store: Arc<dyn ObjectStore>) -> Self {
+        Self { db, object_store }
     }
 }
 
@@ -32,12 +40,34 @@ impl ImageRepo {
 pub trait ImageRepoOps: Send + Sync {
     async fn upload(&self, images: Vec<UploadImage>, user: UserInfo) -> Result<()>;
 
+    async fn save_version(
+        &self,
+        image_id: &str,
+        image: UploadImage,
+        user: UserInfo,
+    ) -> Result<()>;
+
     async fn get_one(
         &self,
         image_id: &str,
         user: UserInfo,
     ) -> Result<Option<ImageData>>;
 
+    async fn get_thumbnail(
+        &self,
+        image_id: &str,
+        size: u32,
+        user: UserInfo,
+    ) -> Result<Option<ImageData>>;
+
+    async fn get_rendition(
+        &self,
+        image_id: &str,
+        resize: Option<ResizeOptions>,
+        format: Option<OutputFormat>,
+        user: UserInfo,
+    ) -> Result<Option<ImageData>>;
+
     async fn get_metadata_for_one(
         &self,
         image_id: &str,
@@ -57,6 +87,8 @@ pub trait ImageRepoOps: Send + Sync {
         user: UserInfo,
     ) -> Result<()>;
 
+    async fn delete_all(&self, user: UserInfo) -> Result<()>;
+
     async fn revert(
         &self,
         image_id: &str,
@@ -79,27 +111,126 @@ pub trait ImageRepoOps: Send + Sync {
 
 #[async_trait]
 impl ImageRepoOps for ImageRepo {
-    /// Upload image(s) to S3 and store metadata in the database.
+    /// Upload image(s) to the object store and store metadata in
+    /// the database.
     async fn upload(
         &self,
         images: Vec<UploadImage>,
         user: UserInfo,
     ) -> Result<()> {
         for image in images {
-            upload_image(&self.db, &self.img_store_client, image, &user)
+            upload_image(self.db.as_ref(), self.object_store.as_ref(), image, &user)
                 .await?;
         }
 
         Ok(())
     }
 
-    /// Get a single image object from S3.
+    /// Upload an edited image to the object store as a new version
+    /// of an existing image and store its metadata in the database.
+    async fn save_version(
+        &self,
+        image_id: &str,
+        image: UploadImage,
+        user: UserInfo,
+    ) -> Result<()> {
+        let info = get_image_info(self.db.as_ref(), image_id, &user.username)
+            .await
+            .map_err(|_| ImageError::NotFound)?;
+
+        // The object key stays the same even if the format changes,
+        // so that all of the image's versions live under one object
+        let image_path = image_object_key(&user.object_base_path, &info);
+        let image_size = image.data.len();
+
+        let version = self
+            .object_store
+            .put(&image_path, image.data.clone())
+            .await
+            .map_err(|e| ImageError::StorageFailure(e.to_string()))?
+            .ok_or(ImageError::UploadFailure)?;
+
+        // If the image was converted, update its content type and
+        // give its name a matching extension
+        if image.content_type != ContentType::from_int(info.content_type) {
+            let new_name = Path::new(&info.name)
+                .with_extension(image.content_type.extension())
+                .to_string_lossy()
+                .into_owned();
+
+            self.db.update_image_format(
+                &info.id,
+                image.content_type.clone(),
+                &new_name,
+                &image_path,
+            )
+            .await
+            .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
+        }
+
+        self.db.insert_image_version(
+            &info.id,
+            &version,
+            image.dimensions,
+            image_size,
+            image.content_type,
+            image.operations.as_deref(),
+        )
+        .await
+        .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
+
+        if let Err(e) = store_thumbnails(
+            self.object_store.as_ref(),
+            &user.object_base_path,
+            &info.id,
+            &version,
+            image.data,
+        )
+        .await {
+            error!("Thumbnail generation failed: {:?}", e);
+        }
+
+        Ok(())
+    }
+
+    /// Get a single image object from the object store.
     async fn get_one(
         &self,
         image_id: &str,
         user: UserInfo,
     ) -> Result<Option<ImageData>> {
-        let image = match get_image_info(&self.db, image_id, &user.username).await {
+        let image = match get_image_info(self.db.as_ref(), image_id, &user.username).await {
+            Ok(img) => img,
+            Err(e) => {
+                error!("Error getting image metadata: {}", e);
+                return Ok(None);
+            }
+        };
+
+        // Object key of the image
+        let image_path = image_object_key(&user.object_base_path, &image);
+
+        let data = read_object(
+            self.object_store.as_ref(), &image_path, Some(&image.version),
+        )
+        .await?;
+
+        let content_type = ContentType::from_int(image.content_type)
+            .to_string();
+
+        Ok(Some(ImageData { name: image.name, content_type, data }))
+    }
+
+    /// Get a thumbnail of the current version of an image from the
+    /// object store, generating the image's thumbnails first if
+    /// they're missing.
+    async fn get_thumbnail(
+        &self,
+        image_id: &str,
+        size: u32,
+        user: UserInfo,
+    ) -> Result<Option<ImageData>> {
+        let image = match get_image_info(self.db.as_ref(), image_id, &user.username).await {
             Ok(img) => img,
             Err(e) => {
                 error!("Error getting image metadata: {}", e);
@@ -107,31 +238,120 @@ impl ImageRepoOps for ImageRepo {
             }
         };
 
-        // S3 object path of the image
-        let image_path = get_object_path(
+        let thumbnail_path = thumbnail_key(
             &user.object_base_path,
             &image.id,
-            &image.name,
+            &image.version,
+            size,
         );
 
-        let data = s3::get_object(
-            &self.img_store_client, &image_path, &image.version,
+        let data = match self.object_store.get(&thumbnail_path, None).await {
+            Ok(data) => data,
+            Err(_) => {
+                // Thumbnails for this version haven't been generated
+                // yet, so make them from the image itself
+                let image_path = image_object_key(&user.object_base_path, &image);
+                let original = read_object(
+                    self.object_store.as_ref(), &image_path, Some(&image.version),
+                )
+                .await?;
+
+                store_thumbnails(
+                    self.object_store.as_ref(),
+                    &user.object_base_path,
+                    &image.id,
+                    &image.version,
+                    original,
+                )
+                .await?
+                .into_iter()
+                .find_map(|(thumbnail_size, data)| {
+                    (thumbnail_size == size).then_some(data)
+                })
+                .ok_or(ImageError::NotFound)?
+            }
+        };
+
+        let content_type = transform::sniff_content_type(&data).to_string();
+
+        Ok(Some(ImageData { name: image.name, content_type, data }))
+    }
+
+    /// Get a resized and/or converted rendition of the current version
+    /// of an image, rendering it and caching it in the object store if
+    /// it hasn't been requested before. Cached renditions are keyed by
+    /// version, so reverting or restoring the image never serves a
+    /// stale one.
+    async fn get_rendition(
+        &self,
+        image_id: &str,
+        resize: Option<ResizeOptions>,
+        format: Option<OutputFormat>,
+        user: UserInfo,
+    ) -> Result<Option<ImageData>> {
+        let image = match get_image_info(self.db.as_ref(), image_id, &user.username).await {
+            Ok(img) => img,
+            Err(e) => {
+                error!("Error getting image metadata: {}", e);
+                return Ok(None);
+            }
+        };
+
+        let content_type = ContentType::from_int(image.content_type);
+        let rendition_path = rendition_key(
+            &user.object_base_path,
+            &image,
+            resize.as_ref(),
+            format.map_or(content_type.clone(), ContentType::from),
+        );
+
+        if let Ok(data) = self.object_store.get(&rendition_path, None).await {
+            let content_type = transform::sniff_content_type(&data).to_string();
+
+            return Ok(Some(ImageData { name: image.name, content_type, data }));
+        }
+
+        let mut operations: Vec<Operation> = Vec::new();
+        if let Some(options) = resize {
+            operations.push(Operation::Resize(options));
+        }
+        if let Some(format) = format {
+            operations.push(Operation::Convert(ConvertOptions {
+                format,
+                quality: None,
+                compression: None,
+            }));
+        }
+
+        let image_path = image_object_key(&user.object_base_path, &image);
+        let original = read_object(
+            self.object_store.as_ref(), &image_path, Some(&image.version),
         )
+        .await?;
+
+        let output = tokio::task::spawn_blocking(move || {
+            transform::apply(&original, &content_type, &operations)
+        })
         .await
-        .map_err(|e| ImageError::S3OperationFailure(e.to_string()))?
-        .body
-        .collect()
-        .await
-        .map_err(|_| ImageError::ReadFailure)?
-        .into_bytes();
+        .map_err(|_| ImageError::ProcessingFailure)??;
 
-        let content_type = ContentType::from_int(image.content_type)
-            .to_string();
+        // Failing to cache the rendition shouldn't fail the request
+        if let Err(e) = self
+            .object_store
+            .put(&rendition_path, output.data.clone())
+            .await
+        {
+            error!("Rendition caching failed: {}", e);
+        }
 
-        Ok(Some(ImageData { content_type, data }))
+        Ok(Some(ImageData {
+            name: image.name,
+            content_type: output.content_type.to_string(),
+            data: output.data,
+        }))
     }
 
-    /// Get a single image object from S3 along with extra
+    /// Get a single image object from the object store along with extra
     /// version info from the database.
     async fn get_metadata_for_one(
         &self,
@@ -146,8 +366,7 @@ impl ImageRepoOps for ImageRepo {
             }
         };
 
-        let image: Option<Image> = match db::find_image_with_version_info(
-            &self.db,
+        let image: Option<Image> = match self.db.find_image_with_version_info(
             &id,
             &user.username,
         )
@@ -169,52 +388,42 @@ impl ImageRepoOps for ImageRepo {
         page: u32,
         limit: u32,
     ) -> Result<ImageList> {
-        // List images in S3
-        let output = s3::get_objects(
-            &self.img_store_client,
-            &user.object_base_path,
-        )
-        .await
-        .map_err(|e| ImageError::S3OperationFailure(e.to_string()))?;
+        // List the user's image objects
+        let mut objects = self
+            .object_store
+            .list(&user.object_base_path)
+            .await
+            .map_err(|e| ImageError::StorageFailure(e.to_string()))?;
 
-        let mut objects = output.contents().to_vec();
         let total = objects.len();
 
         // Sort the objects in descending order by time modified
-        objects.sort_by(|a, b| b.last_modified.cmp(&a.last_modified));
+        objects.sort_by_key(|object| std::cmp::Reverse(object.last_modified));
 
         // Get image metadata from db
-        let db_images = db::find_all_images(&self.db, &user.username)
+        let db_images = self.db.find_all_images(&user.username)
             .await
             .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
 
         // Build map of image metadata
         let mut image_map: HashMap<String, Image> = HashMap::new();
         for image in db_images.iter() {
-            let image_path = get_object_path(
-                &user.object_base_path,
-                &image.id,
-                &image.name,
-            );
+            let image_path = image.object_key.clone().unwrap_or_else(|| {
+                get_object_path(&user.object_base_path, &image.id, &image.name)
+            });
             image_map.insert(image_path, image.clone());
         }
 
         // Calculate pagination
-        let start = ((page - 1) * limit) as usize;
+        // Pages past the end are empty
+        let start = (page.saturating_sub(1) as usize * limit as usize).min(total);
         let end = (start + limit as usize).min(total);
         let has_more = end < total;
 
         // Build image list
         let images: Vec<Image> = objects[start..end]
             .iter()
-            .filter_map(|object| {
-                if let Some(key) = object.key() {
-                    if let Some(image) = image_map.get(key) {
-                        return Some(image.clone());
-                    }
-                }
-                None
-            })
+            .filter_map(|object| image_map.get(&object.key).cloned())
             .collect();
 
         Ok(ImageList { images, total, has_more })
@@ -225,26 +434,54 @@ impl ImageRepoOps for ImageRepo {
         image_id: &str,
         user: UserInfo,
     ) -> Result<()> {
-        let image = get_image_info(&self.db, image_id, &user.username)
+        let image = get_image_info(self.db.as_ref(), image_id, &user.username)
             .await
             .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
 
         // Delete the image's metadata
-        db::delete_image(&self.db, &image.id)
+        self.db.delete_image(&image.id)
             .await
             .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
 
-        // S3 object path of the image
-        let image_path = get_object_path(
-            &user.object_base_path,
-            &image.id,
-            &image.name,
-        );
+        // Object key of the image
+        let image_path = image_object_key(&user.object_base_path, &image);
 
-        // Delete the S3 object
-        s3::delete_object(&self.img_store_client, &image_path)
+        // Delete the image object
+        self.object_store
+            .delete(&image_path)
             .await
-            .map_err(|e| ImageError::S3OperationFailure(e.to_string()))?;
+            .map_err(|e| ImageError::StorageFailure(e.to_string()))?;
+
+        // Permanently delete the thumbnails and renditions of every
+        // version of the image, which have no history to keep
+        for prefix in [
+            thumbnail_prefix(&user.object_base_path, &image.id),
+            rendition_prefix(&user.object_base_path, &image.id),
+        ] {
+            self.object_store
+                .delete_all(&prefix)
+                .await
+                .map_err(|e| ImageError::StorageFailure(e.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete all of a user's images, along with every version of
+    /// their objects, thumbnails, and renditions.
+    async fn delete_all(&self, user: UserInfo) -> Result<()> {
+        // Objects go first, so that a failure leaves the metadata
+        // in place and the deletion can be retried
+        for prefix in user_prefixes(&user.object_base_path) {
+            self.object_store
+                .delete_all(&prefix)
+                .await
+                .map_err(|e| ImageError::StorageFailure(e.to_string()))?;
+        }
+
+        self.db.delete_all_images(&user.username)
+            .await
+            .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
 
         Ok(())
     }
@@ -254,19 +491,19 @@ impl ImageRepoOps for ImageRepo {
         image_id: &str,
         user: UserInfo,
     ) -> Result<Option<String>> {
-        let image = get_image_info(&self.db, image_id, &user.username)
+        let image = get_image_info(self.db.as_ref(), image_id, &user.username)
             .await
             .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
 
         // Revert image to previous version
-        let new_current_version = db::revert_image_version(&self.db, &image.id)
+        let new_current_version = self.db.revert_image_version(&image.id)
             .await
             .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
 
-        if let Some(ref version) = new_current_version {
-            if version == &image.version {
-                return Ok(None);
-            }
+        if let Some(ref version) = new_current_version
+            && version == &image.version
+        {
+            return Ok(None);
         }
 
         Ok(new_current_version)
@@ -277,19 +514,19 @@ impl ImageRepoOps for ImageRepo {
         image_id: &str,
         user: UserInfo,
     ) -> Result<Option<String>> {
-        let image = get_image_info(&self.db, image_id, &user.username)
+        let image = get_image_info(self.db.as_ref(), image_id, &user.username)
             .await
             .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
 
         // Restore image to newer version
-        let new_current_version = db::restore_image_version(&self.db, &image.id)
+        let new_current_version = self.db.restore_image_version(&image.id)
             .await
             .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
 
-        if let Some(ref version) = new_current_version {
-            if version == &image.version {
-                return Ok(None);
-            }
+        if let Some(ref version) = new_current_version
+            && version == &image.version
+        {
+            return Ok(None);
         }
 
         Ok(new_current_version)
@@ -301,12 +538,13 @@ impl ImageRepoOps for ImageRepo {
         new_name: &str,
         user: UserInfo,
     ) -> Result<Option<String>> {
-        let image = get_image_info(&self.db, image_id, &user.username)
+        let image = get_image_info(self.db.as_ref(), image_id, &user.username)
             .await
             .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
 
-        // Update image's name
-        let image_name = db::rename_image(&self.db, &image.id, new_name)
+        // Update image's name, keeping its object key
+        let image_path = image_object_key(&user.object_base_path, &image);
+        let image_name = self.db.rename_image(&image.id, new_name, &image_path)
             .await
             .map_err(|e| ImageError::QueryFailure(e.to_string()))?;
 
@@ -316,19 +554,27 @@ impl ImageRepoOps for ImageRepo {
 
 /// Get image metadata and return it or an error if not found.
 async fn get_image_info(
-    db: &PgPool,
+    db: &dyn ImageDb,
     image_id: &str,
     username: &str,
 ) -> anyhow::Result<ImageInfo> {
     let id = Uuid::parse_str(image_id)?;
-    if let Some(image) = db::find_image(db, &id, username).await? {
+    if let Some(image) = db.find_image(&id, username).await? {
         Ok(image)
     } else {
         anyhow::bail!("Image not found");
     }
 }
 
-/// Get the S3 object path of the image.
+/// Get the object key of an image, deriving it from the image's
+/// name if it wasn't stored when the image was uploaded.
+fn image_object_key(base_path: &str, image: &ImageInfo) -> String {
+    image.object_key.clone().unwrap_or_else(|| {
+        get_object_path(base_path, &image.id, &image.name)
+    })
+}
+
+/// Get the object path of the image.
 fn get_object_path(
     base_path: &str,
     image_id: &Uuid,
@@ -342,10 +588,10 @@ fn get_object_path(
     format!("{}/{}.{}", base_path, image_id, extension)
 }
 
-/// Upload an image to S3 and store metadata in the database.
+/// Upload an image to the object store and store metadata in the database.
 async fn upload_image(
-    db: &PgPool,
-    s3_client: &S3Client,
+    db: &dyn ImageDb,
+    store: &dyn ObjectStore,
     image: UploadImage,
     user: &UserInfo,
 ) -> Result<()> {
@@ -353,8 +599,7 @@ async fn upload_image(
 
     // If an image by the given name exists for this user,
     // get the image id; otherwise, make a new one
-    let image_id: Uuid = if let Ok(Some(image_id)) = db::find_image_id_by_name(
-        db,
+    let image_id: Uuid = if let Ok(Some(image_id)) = db.find_image_id_by_name(
         &image.name,
         &user.username,
     )
@@ -365,48 +610,169 @@ async fn upload_image(
         Uuid::now_v7()
     };
 
-    let image_path = get_object_path(
-        &user.object_base_path,
-        &image_id,
-        &image.name,
-    );
+    // Reuse the existing image's object key, if it has one
+    let existing_image = if is_new {
+        None
+    } else {
+        db.find_image(&image_id, &user.username)
+            .await
+            .map_err(|e| ImageError::QueryFailure(e.to_string()))?
+    };
+
+    let image_path = match existing_image {
+        Some(ref info) => image_object_key(&user.object_base_path, info),
+        None => get_object_path(&user.object_base_path, &image_id, &image.name),
+    };
     let image_size = image.data.len();
 
-    // Upload the image to the S3 bucket and get
+    // Upload the image to the object store and get
     // the image's version id
-    let output = s3::upload_object(
-        s3_client,
-        image.data,
-        &image_path,
-    )
-    .await
-    .map_err(|e| ImageError::S3OperationFailure(e.to_string()))?;
+    let version = store
+        .put(&image_path, image.data.clone())
+        .await
+        .map_err(|e| ImageError::StorageFailure(e.to_string()))?;
 
     // If it's a new image, create a db record for it
-    if is_new && let Err(e) = db::insert_image(
-        db,
+    if is_new && let Err(e) = db.insert_image(
         &image_id,
         &image.name,
-        image.content_type,
+        image.content_type.clone(),
         &user.username,
+        &image_path,
     )
     .await {
         error!("Image insert failed: {}", e);
     }
 
-    if let Some(version) = output.version_id() {
+    if let Some(ref version) = version {
         // Add the image object version in the db
-        if let Err(e) = db::insert_image_version(
-            db,
+        if let Err(e) = db.insert_image_version(
             &image_id,
             version,
             image.dimensions,
             image_size,
+            image.content_type,
+            image.operations.as_deref(),
         )
         .await {
             error!("Image version insert failed: {}", e);
         }
+
+        if let Err(e) = store_thumbnails(
+            store,
+            &user.object_base_path,
+            &image_id,
+            version,
+            image.data,
+        )
+        .await {
+            error!("Thumbnail generation failed: {:?}", e);
+        }
     }
 
     Ok(())
 }
+
+/// Get the key prefix under which an image's thumbnails are stored.
+/// It's kept outside the user's base path so that thumbnails don't
+/// show up when listing the user's images.
+fn thumbnail_prefix(base_path: &str, image_id: &Uuid) -> String {
+    format!("thumbnails/{}/{}/", base_path, image_id)
+}
+
+/// Get the prefixes of all of a user's objects.
+fn user_prefixes(base_path: &str) -> [String; 3] {
+    [
+        format!("{}/", base_path),
+        format!("thumbnails/{}/", base_path),
+        format!("renditions/{}/", base_path),
+    ]
+}
+
+/// Get the object key of a thumbnail of an image version.
+fn thumbnail_key(
+    base_path: &str,
+    image_id: &Uuid,
+    version: &str,
+    size: u32,
+) -> String {
+    format!("{}{}/{}", thumbnail_prefix(base_path, image_id), version, size)
+}
+
+/// Get the key prefix under which an image's cached renditions
+/// are stored, outside the user's base path like thumbnails.
+fn rendition_prefix(base_path: &str, image_id: &Uuid) -> String {
+    format!("renditions/{}/{}/", base_path, image_id)
+}
+
+/// Get the object key of a rendition of the current version of
+/// an image, derived from the version and rendering parameters.
+fn rendition_key(
+    base_path: &str,
+    image: &ImageInfo,
+    resize: Option<&ResizeOptions>,
+    content_type: ContentType,
+) -> String {
+    let dimension = |value: Option<u32>| {
+        value.map_or("auto".to_string(), |v| v.to_string())
+    };
+
+    let size = match resize {
+        Some(options) => format!(
+            "{}x{}-{:?}",
+            dimension(options.width),
+            dimension(options.height),
+            options.fit,
+        )
+        .to_lowercase(),
+        None => "original".to_string(),
+    };
+
+    format!(
+        "{}{}/{}.{}",
+        rendition_prefix(base_path, &image.id),
+        image.version,
+        size,
+        content_type.extension(),
+    )
+}
+
+/// Read the contents of an object.
+async fn read_object(
+    store: &dyn ObjectStore,
+    object_key: &str,
+    version_id: Option<&str>,
+) -> Result<Bytes> {
+    store
+        .get(object_key, version_id)
+        .await
+        .map_err(|e| ImageError::StorageFailure(e.to_string()))
+}
+
+/// Generate thumbnails of an image version, upload them to the
+/// object store, and return their sizes and data.
+async fn store_thumbnails(
+    store: &dyn ObjectStore,
+    base_path: &str,
+    image_id: &Uuid,
+    version: &str,
+    data: Bytes,
+) -> Result<Vec<(u32, Bytes)>> {
+    let thumbnails = tokio::task::spawn_blocking(move || transform::thumbnails(&data))
+        .await
+        .map_err(|_| ImageError::ProcessingFailure)??;
+
+    let mut stored = Vec::with_capacity(thumbnails.len());
+    for (size, thumbnail) in thumbnails {
+        let thumbnail_path = thumbnail_key(base_path, image_id, version, size);
+
+        store
+            .put(&thumbnail_path, thumbnail.data.clone())
+            .await
+            .map_err(|e| ImageError::StorageFailure(e.to_string()))?;
+
+        stored.push((size, thumbnail.data));
+    }
+
+    Ok(stored)
+}
@@ -0,0 +1,17 @@
+//! In-memory implementations of the repositories, for tests that
+//! exercise the API without Postgres or S3. The image repository
+//! is the real one, with only its queries answered from memory
+//! (see `ImageRepo::in_memory`).
+
+mod audit_log_repo;
+mod image_repo;
+mod login_attempt_repo;
+mod refresh_token_repo;
+mod totp_repo;
+mod user_repo;
+
+pub use audit_log_repo::InMemoryAuditLogRepo;
+pub use login_attempt_repo::InMemoryLoginAttemptRepo;
+pub use refresh_token_repo::InMemoryRefreshTokenRepo;
+pub use totp_repo::InMemoryTotpRepo;
+pub use user_repo::InMemoryUserRepo;
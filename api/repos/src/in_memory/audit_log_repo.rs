@@ -0,0 +1,47 @@
+use async_trait::async_trait;
+use sqlx::Error as SqlxError;
+use std::sync::Mutex;
+
+use models::AuditEvent;
+
+use crate::AuditLogRepoOps;
+
+/// Audit log kept in memory.
+#[derive(Default)]
+pub struct InMemoryAuditLogRepo {
+    /// Events, oldest first
+    events: Mutex<Vec<AuditEvent>>,
+}
+
+impl InMemoryAuditLogRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl AuditLogRepoOps for InMemoryAuditLogRepo {
+    async fn record(&self, event: &AuditEvent) -> Result<(), SqlxError> {
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+
+    async fn find_by_username(
+        &self,
+        username: &str,
+        limit: i64,
+    ) -> Result<Vec<AuditEvent>, SqlxError> {
+        let events = self
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|event| event.username.as_deref() == Some(username))
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect();
+
+        Ok(events)
+    }
+}
@@ -0,0 +1,310 @@
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use models::{ContentType, Image, ImageInfo, Operation};
+use storage::ObjectStore;
+
+use crate::image_db::ImageDb;
+use crate::ImageRepo;
+
+/// A row of `image_version`
+struct VersionRow {
+    version: String,
+    ts: DateTime<Utc>,
+    current: bool,
+    dimensions: (u32, u32),
+    size: usize,
+    content_type: Option<ContentType>,
+    operations: Option<Vec<Operation>>,
+}
+
+/// A row of `image`, along with its versions, oldest first
+struct ImageRow {
+    id: Uuid,
+    name: String,
+    content_type: ContentType,
+    username: String,
+    created_at: DateTime<Utc>,
+    object_key: Option<String>,
+    versions: Vec<VersionRow>,
+}
+
+impl ImageRow {
+    /// Get the index of the current version.
+    fn current_index(&self) -> Option<usize> {
+        self.versions.iter().position(|version| version.current)
+    }
+
+    /// Make a version current, returning its id.
+    fn make_current(&mut self, index: usize) -> String {
+        for (i, version) in self.versions.iter_mut().enumerate() {
+            version.current = i == index;
+        }
+
+        self.versions[index].version.clone()
+    }
+
+    fn version_content_type(&self, version: &VersionRow) -> ContentType {
+        version
+            .content_type
+            .clone()
+            .unwrap_or_else(|| self.content_type.clone())
+    }
+
+    fn to_info(&self) -> Option<ImageInfo> {
+        let version = &self.versions[self.current_index()?];
+
+        Some(ImageInfo {
+            id: self.id,
+            name: self.name.clone(),
+            username: self.username.clone(),
+            content_type: self.version_content_type(version) as i32,
+            version: version.version.clone(),
+            object_key: self.object_key.clone(),
+        })
+    }
+
+    fn to_image(&self) -> Option<Image> {
+        let index = self.current_index()?;
+        let version = &self.versions[index];
+        let version_count = self.versions.len() as i64;
+        let version_index = index as i64 + 1;
+
+        Some(Image {
+            id: self.id,
+            name: self.name.clone(),
+            content_type: self.version_content_type(version).to_string(),
+            created_at: self.created_at.to_string(),
+            last_modified: version.ts.to_string(),
+            version: version.version.clone(),
+            width: version.dimensions.0 as i32,
+            height: version.dimensions.1 as i32,
+            size: version.size as i64,
+            version_count,
+            version_index,
+            latest_version: version_index == version_count,
+            initial_version: version_index == 1,
+            operations: version.operations.clone().unwrap_or_default(),
+            object_key: self.object_key.clone(),
+        })
+    }
+}
+
+/// Image metadata kept in memory, answering the same queries as
+/// the `image` and `image_version` tables.
+#[derive(Default)]
+struct InMemoryImageDb {
+    images: Mutex<HashMap<Uuid, ImageRow>>,
+}
+
+impl ImageRepo {
+    /// Make an image repository whose metadata is kept in memory
+    /// and whose objects are kept in the given store. Only its
+    /// queries are faked, so object keys, thumbnail and rendition
+    /// caching, and versions are handled as in production.
+    pub fn in_memory(object_store: Arc<dyn ObjectStore>) -> Self {
+        Self::with_db(Arc::new(InMemoryImageDb::default()), object_store)
+    }
+}
+
+#[async_trait]
+impl ImageDb for InMemoryImageDb {
+    async fn insert_image(
+        &self,
+        id: &Uuid,
+        name: &str,
+        content_type: ContentType,
+        username: &str,
+        object_key: &str,
+    ) -> Result<()> {
+        let mut images = self.images.lock().unwrap();
+
+        // ON CONFLICT (name, username) DO NOTHING
+        if images.values().any(|i| i.name == name && i.username == username) {
+            return Ok(());
+        }
+
+        images.insert(*id, ImageRow {
+            id: *id,
+            name: name.to_string(),
+            content_type,
+            username: username.to_string(),
+            created_at: Utc::now(),
+            object_key: Some(object_key.to_string()),
+            versions: Vec::new(),
+        });
+
+        Ok(())
+    }
+
+    async fn insert_image_version(
+        &self,
+        image_id: &Uuid,
+        version: &str,
+        dimensions: (u32, u32),
+        size: usize,
+        content_type: ContentType,
+        operations: Option<&[Operation]>,
+    ) -> Result<()> {
+        let mut images = self.images.lock().unwrap();
+        let Some(image) = images.get_mut(image_id) else {
+            bail!("Image {} doesn't exist", image_id);
+        };
+
+        image.versions.push(VersionRow {
+            version: version.to_string(),
+            ts: Utc::now(),
+            current: true,
+            dimensions,
+            size,
+            content_type: Some(content_type),
+            operations: operations.map(<[Operation]>::to_vec),
+        });
+        image.make_current(image.versions.len() - 1);
+
+        Ok(())
+    }
+
+    async fn find_image(&self, id: &Uuid, username: &str) -> Result<Option<ImageInfo>> {
+        let images = self.images.lock().unwrap();
+
+        Ok(images
+            .get(id)
+            .filter(|image| image.username == username)
+            .and_then(ImageRow::to_info))
+    }
+
+    async fn find_image_with_version_info(
+        &self,
+        id: &Uuid,
+        username: &str,
+    ) -> Result<Option<Image>> {
+        let images = self.images.lock().unwrap();
+
+        Ok(images
+            .get(id)
+            .filter(|image| image.username == username)
+            .and_then(ImageRow::to_image))
+    }
+
+    async fn find_image_id_by_name(&self, name: &str, username: &str) -> Result<Option<Uuid>> {
+        let images = self.images.lock().unwrap();
+
+        Ok(images
+            .values()
+            .find(|image| image.name == name && image.username == username)
+            .map(|image| image.id))
+    }
+
+    async fn find_all_images(&self, username: &str) -> Result<Vec<Image>> {
+        let images = self.images.lock().unwrap();
+
+        Ok(images
+            .values()
+            .filter(|image| image.username == username)
+            .filter_map(ImageRow::to_image)
+            .collect())
+    }
+
+    async fn delete_image(&self, image_id: &Uuid) -> Result<()> {
+        self.images.lock().unwrap().remove(image_id);
+        Ok(())
+    }
+
+    async fn delete_all_images(&self, username: &str) -> Result<()> {
+        self.images
+            .lock()
+            .unwrap()
+            .retain(|_, image| image.username != username);
+
+        Ok(())
+    }
+
+    async fn revert_image_version(&self, image_id: &Uuid) -> Result<Option<String>> {
+        let mut images = self.images.lock().unwrap();
+        let Some(image) = images.get_mut(image_id) else {
+            return Ok(None);
+        };
+
+        match image.current_index() {
+            Some(index) if index > 0 => Ok(Some(image.make_current(index - 1))),
+            _ => Ok(None),
+        }
+    }
+
+    async fn restore_image_version(&self, image_id: &Uuid) -> Result<Option<String>> {
+        let mut images = self.images.lock().unwrap();
+        let Some(image) = images.get_mut(image_id) else {
+            return Ok(None);
+        };
+
+        match image.current_index() {
+            Some(index) if index + 1 < image.versions.len() => {
+                Ok(Some(image.make_current(index + 1)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn rename_image(
+        &self,
+        image_id: &Uuid,
+        new_name: &str,
+        object_key: &str,
+    ) -> Result<Option<String>> {
+        let mut images = self.images.lock().unwrap();
+        let Some(username) = images.get(image_id).map(|image| image.username.clone()) else {
+            bail!("Image {} doesn't exist", image_id);
+        };
+
+        let name_taken = images.values().any(|image| {
+            image.id != *image_id && image.username == username && image.name == new_name
+        });
+        if name_taken {
+            bail!("duplicate key value violates unique constraint \"uniq_name_username\"");
+        }
+
+        let image = images.get_mut(image_id).expect("image was found above");
+        image.name = new_name.to_string();
+        image.object_key.get_or_insert_with(|| object_key.to_string());
+
+        Ok(Some(image.name.clone()))
+    }
+
+    async fn update_image_format(
+        &self,
+        image_id: &Uuid,
+        content_type: ContentType,
+        new_name: &str,
+        object_key: &str,
+    ) -> Result<()> {
+        let mut images = self.images.lock().unwrap();
+        let Some(username) = images.get(image_id).map(|image| image.username.clone()) else {
+            return Ok(());
+        };
+
+        let name_taken = images.values().any(|image| {
+            image.id != *image_id && image.username == username && image.name == new_name
+        });
+
+        let image = images.get_mut(image_id).expect("image was found above");
+
+        // Pin the content type of versions saved before
+        // per-version content types were recorded
+        for version in image.versions.iter_mut() {
+            version.content_type.get_or_insert_with(|| image.content_type.clone());
+        }
+
+        image.content_type = content_type;
+        image.object_key.get_or_insert_with(|| object_key.to_string());
+        if !name_taken {
+            image.name = new_name.to_string();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::Error as SqlxError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use models::LoginAttempts;
+
+use crate::LoginAttemptRepoOps;
+
+/// Login attempt repository kept in memory.
+#[derive(Default)]
+pub struct InMemoryLoginAttemptRepo {
+    /// Failed attempts, by key
+    attempts: Mutex<HashMap<String, LoginAttempts>>,
+}
+
+impl InMemoryLoginAttemptRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepoOps for InMemoryLoginAttemptRepo {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempts>, SqlxError> {
+        Ok(self.attempts.lock().unwrap().get(key).cloned())
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        reset_before: DateTime<Utc>,
+    ) -> Result<LoginAttempts, SqlxError> {
+        let now = Utc::now();
+        let mut attempts = self.attempts.lock().unwrap();
+
+        let entry = attempts
+            .entry(key.to_string())
+            .or_insert_with(|| LoginAttempts {
+                key: key.to_string(),
+                failures: 0,
+                last_failure_at: now,
+                locked_until: None,
+            });
+
+        if entry.last_failure_at < reset_before {
+            entry.failures = 0;
+        }
+        entry.failures += 1;
+        entry.last_failure_at = now;
+
+        Ok(entry.clone())
+    }
+
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), SqlxError> {
+        if let Some(attempts) = self.attempts.lock().unwrap().get_mut(key) {
+            attempts.locked_until = Some(until);
+        }
+
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), SqlxError> {
+        self.attempts.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::Error as SqlxError;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use models::RefreshToken;
+
+use crate::refresh_token_repo::hash_token;
+use crate::RefreshTokenRepoOps;
+
+/// Refresh token repository kept in memory.
+#[derive(Default)]
+pub struct InMemoryRefreshTokenRepo {
+    /// Refresh tokens, by token hash
+    tokens: Mutex<HashMap<String, RefreshToken>>,
+}
+
+impl InMemoryRefreshTokenRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Save a token, continuing the family of `previous` if given,
+    /// or else starting a new one.
+    fn insert(
+        &self,
+        username: &str,
+        token: &str,
+        previous: Option<&RefreshToken>,
+        user_agent: Option<String>,
+        client_ip: Option<String>,
+    ) -> RefreshToken {
+        let now = Utc::now();
+        let id = Uuid::now_v7();
+        let token_hash = hash_token(token);
+        let refresh_token = RefreshToken {
+            id,
+            username: username.to_string(),
+            token_hash: token_hash.clone(),
+            expires_at: now + Duration::days(7),
+            is_used: false,
+            used_at: None,
+            created_at: now,
+            last_used_at: now,
+            family_id: previous.map_or(id, |previous| previous.family_id),
+            user_agent,
+            client_ip,
+            session_created_at: previous.map_or(now, |previous| previous.session_created_at),
+        };
+
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(token_hash, refresh_token.clone());
+
+        refresh_token
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepoOps for InMemoryRefreshTokenRepo {
+    async fn create_token(
+        &self,
+        username: &str,
+        token: &str,
+        user_agent: Option<&str>,
+        client_ip: Option<&str>,
+    ) -> Result<RefreshToken, SqlxError> {
+        Ok(self.insert(
+            username,
+            token,
+            None,
+            user_agent.map(String::from),
+            client_ip.map(String::from),
+        ))
+    }
+
+    async fn rotate_token(
+        &self,
+        previous: &RefreshToken,
+        token: &str,
+    ) -> Result<RefreshToken, SqlxError> {
+        Ok(self.insert(
+            &previous.username,
+            token,
+            Some(previous),
+            previous.user_agent.clone(),
+            previous.client_ip.clone(),
+        ))
+    }
+
+    async fn find_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<RefreshToken>, SqlxError> {
+        Ok(self.tokens.lock().unwrap().get(&hash_token(token)).cloned())
+    }
+
+    async fn find_active_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Vec<RefreshToken>, SqlxError> {
+        let mut refresh_tokens: Vec<RefreshToken> = self
+            .tokens
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|refresh_token| {
+                refresh_token.username == username && refresh_token.is_valid()
+            })
+            .cloned()
+            .collect();
+
+        refresh_tokens.sort_by_key(|refresh_token| Reverse(refresh_token.last_used_at));
+        Ok(refresh_tokens)
+    }
+
+    async fn delete_family(&self, family_id: Uuid) -> Result<(), SqlxError> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .retain(|_, refresh_token| refresh_token.family_id != family_id);
+
+        Ok(())
+    }
+
+    async fn delete_user_family(
+        &self,
+        username: &str,
+        family_id: Uuid,
+    ) -> Result<bool, SqlxError> {
+        let mut tokens = self.tokens.lock().unwrap();
+        let before = tokens.len();
+        tokens.retain(|_, refresh_token| {
+            refresh_token.username != username || refresh_token.family_id != family_id
+        });
+
+        Ok(tokens.len() < before)
+    }
+
+    async fn update_last_used(&self, token: &str) -> Result<(), SqlxError> {
+        if let Some(refresh_token) = self.tokens.lock().unwrap().get_mut(&hash_token(token)) {
+            refresh_token.last_used_at = Utc::now();
+        }
+
+        Ok(())
+    }
+
+    async fn delete_token(&self, token: &str) -> Result<(), SqlxError> {
+        self.tokens.lock().unwrap().remove(&hash_token(token));
+        Ok(())
+    }
+
+    async fn delete_all_user_tokens(&self, username: &str) -> Result<(), SqlxError> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .retain(|_, refresh_token| refresh_token.username != username);
+
+        Ok(())
+    }
+
+    async fn mark_token_used(&self, token: &str) -> Result<bool, SqlxError> {
+        let mut tokens = self.tokens.lock().unwrap();
+        let Some(refresh_token) = tokens
+            .get_mut(&hash_token(token))
+            .filter(|refresh_token| !refresh_token.is_used)
+        else {
+            return Ok(false);
+        };
+
+        refresh_token.is_used = true;
+        refresh_token.used_at = Some(Utc::now());
+        Ok(true)
+    }
+
+    async fn purge_tokens(
+        &self,
+        used_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<u64, SqlxError> {
+        let mut tokens = self.tokens.lock().unwrap();
+
+        let purged: Vec<String> = tokens
+            .values()
+            .filter(|refresh_token| {
+                refresh_token.is_expired()
+                    || refresh_token.used_at.is_some_and(|used_at| used_at < used_before)
+            })
+            .take(limit as usize)
+            .map(|refresh_token| refresh_token.token_hash.clone())
+            .collect();
+
+        for token_hash in &purged {
+            tokens.remove(token_hash);
+        }
+
+        Ok(purged.len() as u64)
+    }
+}
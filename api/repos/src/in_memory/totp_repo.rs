@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use sqlx::Error as SqlxError;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use models::TotpCredential;
+
+use crate::TotpRepoOps;
+
+/// TOTP secret and recovery code repository kept in memory.
+#[derive(Default)]
+pub struct InMemoryTotpRepo {
+    /// TOTP secrets, by username
+    credentials: Mutex<HashMap<String, TotpCredential>>,
+
+    /// Hashes of unused recovery codes, by username
+    recovery_codes: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl InMemoryTotpRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl TotpRepoOps for InMemoryTotpRepo {
+    async fn find(&self, username: &str) -> Result<Option<TotpCredential>, SqlxError> {
+        Ok(self.credentials.lock().unwrap().get(username).cloned())
+    }
+
+    async fn save_pending(&self, username: &str, secret: &str) -> Result<(), SqlxError> {
+        let mut credentials = self.credentials.lock().unwrap();
+        if credentials.get(username).is_some_and(|c| c.enabled) {
+            return Ok(());
+        }
+
+        credentials.insert(
+            username.to_string(),
+            TotpCredential {
+                username: username.to_string(),
+                secret: secret.to_string(),
+                enabled: false,
+                last_used_step: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn enable(
+        &self,
+        username: &str,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), SqlxError> {
+        if let Some(credential) = self.credentials.lock().unwrap().get_mut(username) {
+            credential.enabled = true;
+            credential.last_used_step = Some(step);
+        }
+
+        self.recovery_codes.lock().unwrap().insert(
+            username.to_string(),
+            recovery_code_hashes.iter().cloned().collect(),
+        );
+
+        Ok(())
+    }
+
+    async fn use_step(&self, username: &str, step: i64) -> Result<bool, SqlxError> {
+        let mut credentials = self.credentials.lock().unwrap();
+        let Some(credential) = credentials.get_mut(username) else {
+            return Ok(false);
+        };
+
+        if credential.last_used_step.is_some_and(|last| last >= step) {
+            return Ok(false);
+        }
+
+        credential.last_used_step = Some(step);
+        Ok(true)
+    }
+
+    async fn use_recovery_code(&self, username: &str, code_hash: &str) -> Result<bool, SqlxError> {
+        let is_used = self
+            .recovery_codes
+            .lock()
+            .unwrap()
+            .get_mut(username)
+            .is_some_and(|codes| codes.remove(code_hash));
+
+        Ok(is_used)
+    }
+}
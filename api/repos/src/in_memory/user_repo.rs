@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use config::PasswordHashConfig;
+use models::{User, UserInfo};
+
+use crate::{PasswordHasher, UserRepoError, UserRepoOps};
+
+type Result<T> = anyhow::Result<T, UserRepoError>;
+
+/// User repository kept in memory. Passwords are hashed with the
+/// lowest Argon2id costs, since it only backs tests.
+pub struct InMemoryUserRepo {
+    /// Users and their password hashes, by username
+    users: Mutex<HashMap<String, (UserInfo, String)>>,
+    hasher: PasswordHasher,
+}
+
+impl Default for InMemoryUserRepo {
+    fn default() -> Self {
+        let hasher = PasswordHasher::new(&PasswordHashConfig {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        })
+        .expect("minimum Argon2 parameters are valid");
+
+        Self {
+            users: Mutex::default(),
+            hasher,
+        }
+    }
+}
+
+impl InMemoryUserRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UserRepoOps for InMemoryUserRepo {
+    async fn create(&self, user: &User) -> Result<UserInfo> {
+        let password_hash = self.hasher.hash(&user.password).await?;
+
+        let mut users = self.users.lock().unwrap();
+        if users.keys().any(|name| name.eq_ignore_ascii_case(&user.username)) {
+            return Err(UserRepoError::UsernameTaken);
+        }
+
+        let user_info = UserInfo {
+            username: user.username.clone(),
+            object_base_path: Uuid::now_v7().to_string(),
+        };
+        users.insert(
+            user.username.clone(),
+            (user_info.clone(), password_hash),
+        );
+
+        Ok(user_info)
+    }
+
+    async fn authorize(&self, user: &User) -> Result<bool> {
+        let password_hash = self
+            .users
+            .lock()
+            .unwrap()
+            .get(&user.username)
+            .map(|(_, password_hash)| password_hash.clone());
+
+        let is_authorized = match password_hash {
+            Some(password_hash) => self.hasher.verify(&user.password, &password_hash).await?,
+            None => self.hasher.verify_missing(&user.password).await?,
+        };
+
+        Ok(is_authorized)
+    }
+
+    async fn find(&self, username: &str) -> Result<Option<UserInfo>> {
+        let user_info = self
+            .users
+            .lock()
+            .unwrap()
+            .get(username)
+            .map(|(user_info, _)| user_info.clone());
+
+        Ok(user_info)
+    }
+
+    async fn username_taken(&self, username: &str) -> Result<bool> {
+        let is_taken = self
+            .users
+            .lock()
+            .unwrap()
+            .keys()
+            .any(|name| name.eq_ignore_ascii_case(username));
+
+        Ok(is_taken)
+    }
+
+    async fn update_password(&self, username: &str, password: &str) -> Result<()> {
+        let password_hash = self.hasher.hash(password).await?;
+
+        if let Some((_, stored_hash)) = self.users.lock().unwrap().get_mut(username) {
+            *stored_hash = password_hash;
+        }
+
+        Ok(())
+    }
+
+    async fn delete(&self, username: &str) -> Result<()> {
+        self.users.lock().unwrap().remove(username);
+        Ok(())
+    }
+}
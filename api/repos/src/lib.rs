@@ -1,7 +1,23 @@
+mod audit_log_repo;
+mod image_db;
 mod image_repo;
+#[cfg(feature = "test-util")]
+mod in_memory;
+mod login_attempt_repo;
+mod password;
 mod refresh_token_repo;
+mod totp_repo;
 mod user_repo;
 
+pub use audit_log_repo::{AuditLogRepo, AuditLogRepoOps};
 pub use image_repo::{ImageRepo, ImageRepoOps};
+#[cfg(feature = "test-util")]
+pub use in_memory::{
+    InMemoryAuditLogRepo, InMemoryLoginAttemptRepo, InMemoryRefreshTokenRepo,
+    InMemoryTotpRepo, InMemoryUserRepo,
+};
+pub use login_attempt_repo::{LoginAttemptRepo, LoginAttemptRepoOps};
+pub use password::{PasswordHashError, PasswordHasher};
 pub use refresh_token_repo::{RefreshTokenRepo, RefreshTokenRepoOps};
-pub use user_repo::{UserRepo, UserRepoOps};
+pub use totp_repo::{TotpRepo, TotpRepoOps};
+pub use user_repo::{UserRepo, UserRepoError, UserRepoOps};
@@ -0,0 +1,101 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{Error as SqlxError, PgPool};
+
+use models::LoginAttempts;
+
+#[derive(Clone)]
+pub struct LoginAttemptRepo {
+    db: PgPool,
+}
+
+impl LoginAttemptRepo {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+pub trait LoginAttemptRepoOps: Send + Sync {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempts>, SqlxError>;
+
+    /// Count a failed attempt, starting the count over if the last
+    /// failure was before `reset_before`.
+    async fn record_failure(
+        &self,
+        key: &str,
+        reset_before: DateTime<Utc>,
+    ) -> Result<LoginAttempts, SqlxError>;
+
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), SqlxError>;
+
+    async fn clear(&self, key: &str) -> Result<(), SqlxError>;
+}
+
+#[async_trait]
+impl LoginAttemptRepoOps for LoginAttemptRepo {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempts>, SqlxError> {
+        let attempts = sqlx::query_as::<_, LoginAttempts>(
+            r#"
+            SELECT key, failures, last_failure_at, locked_until
+            FROM login_attempts WHERE key = $1
+            "#,
+        )
+        .bind(key)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(attempts)
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        reset_before: DateTime<Utc>,
+    ) -> Result<LoginAttempts, SqlxError> {
+        // Counted in one statement so that concurrent failures
+        // on other replicas aren't lost
+        let attempts = sqlx::query_as::<_, LoginAttempts>(
+            r#"
+            INSERT INTO login_attempts (key, failures, last_failure_at)
+            VALUES ($1, 1, NOW())
+            ON CONFLICT (key) DO UPDATE SET
+                failures = CASE
+                    WHEN login_attempts.last_failure_at < $2 THEN 1
+                    ELSE login_attempts.failures + 1
+                END,
+                last_failure_at = NOW()
+            RETURNING key, failures, last_failure_at, locked_until
+            "#,
+        )
+        .bind(key)
+        .bind(reset_before)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(attempts)
+    }
+
+    async fn lock(&self, key: &str, until: DateTime<Utc>) -> Result<(), SqlxError> {
+        sqlx::query!(
+            "UPDATE login_attempts SET locked_until = $2 WHERE key = $1",
+            key,
+            until,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), SqlxError> {
+        sqlx::query!(
+            "DELETE FROM login_attempts WHERE key = $1",
+            key,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+//! Password Hashing
+//!
+//! Passwords are hashed with Argon2id. Hashes made by pgcrypto's
+//! MD5-crypt before the move to Argon2id are still verified, and
+//! are flagged for rehashing so they're upgraded on the next login.
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, SaltString},
+    Algorithm, Argon2, Params, PasswordHasher as _, PasswordVerifier as _, Version,
+};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use tokio::task::JoinError;
+
+use config::PasswordHashConfig;
+
+type Result<T> = anyhow::Result<T, PasswordHashError>;
+
+/// Prefix of MD5-crypt hashes, as made by `crypt(_, gen_salt('md5'))`
+const MD5_CRYPT_PREFIX: &str = "$1$";
+
+/// Failure to hash or verify a password, such as a malformed
+/// stored hash.
+#[derive(Debug)]
+pub struct PasswordHashError(String);
+
+impl From<argon2::password_hash::Error> for PasswordHashError {
+    fn from(value: argon2::password_hash::Error) -> Self {
+        PasswordHashError(value.to_string())
+    }
+}
+
+impl From<JoinError> for PasswordHashError {
+    fn from(value: JoinError) -> Self {
+        PasswordHashError(format!("hashing task failed: {}", value))
+    }
+}
+
+impl Display for PasswordHashError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "Password hashing failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for PasswordHashError {}
+
+#[derive(Clone)]
+pub struct PasswordHasher {
+    argon2: Argon2<'static>,
+
+    /// Hash of no real password, made with the current costs, so that
+    /// unknown usernames take as long to reject as wrong passwords
+    dummy_hash: String,
+}
+
+impl Default for PasswordHasher {
+    fn default() -> Self {
+        Self::new(&PasswordHashConfig::default())
+            .expect("default Argon2 parameters are valid")
+    }
+}
+
+impl PasswordHasher {
+    pub fn new(config: &PasswordHashConfig) -> anyhow::Result<Self> {
+        let params = Params::new(
+            config.memory_kib,
+            config.iterations,
+            config.parallelism,
+            None,
+        )
+        .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {}", e))?;
+
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        let dummy_hash = argon2
+            .hash_password(b"", &SaltString::generate(&mut OsRng))
+            .map_err(|e| anyhow::anyhow!("Failed to make dummy hash: {}", e))?
+            .to_string();
+
+        Ok(Self { argon2, dummy_hash })
+    }
+
+    /// Hash a password with a new random salt.
+    pub async fn hash(&self, password: &str) -> Result<String> {
+        let argon2 = self.argon2.clone();
+        let password = password.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let salt = SaltString::generate(&mut OsRng);
+            Ok(argon2.hash_password(password.as_bytes(), &salt)?.to_string())
+        })
+        .await?
+    }
+
+    /// Check a password against a stored Argon2id or MD5-crypt hash.
+    pub async fn verify(&self, password: &str, hash: &str) -> Result<bool> {
+        let argon2 = self.argon2.clone();
+        let password = password.to_string();
+        let hash = hash.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            if hash.starts_with(MD5_CRYPT_PREFIX) {
+                return Ok(pwhash::md5_crypt::verify(&password, &hash));
+            }
+
+            let parsed = PasswordHash::new(&hash)?;
+            Ok(argon2.verify_password(password.as_bytes(), &parsed).is_ok())
+        })
+        .await?
+    }
+
+    /// Spend the same time as `verify` for a user that doesn't exist.
+    /// Always false.
+    pub async fn verify_missing(&self, password: &str) -> Result<bool> {
+        self.verify(password, &self.dummy_hash).await?;
+        Ok(false)
+    }
+
+    /// Whether a stored hash should be replaced, either because it
+    /// isn't Argon2id or because it was made with different costs.
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return true;
+        };
+
+        if parsed.algorithm != Algorithm::Argon2id.ident() {
+            return true;
+        }
+
+        Params::try_from(&parsed).map_or(true, |params| {
+            let current = self.argon2.params();
+            params.m_cost() != current.m_cost()
+                || params.t_cost() != current.t_cost()
+                || params.p_cost() != current.p_cost()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cheap_hasher(iterations: u32) -> PasswordHasher {
+        PasswordHasher::new(&PasswordHashConfig {
+            memory_kib: 64,
+            iterations,
+            parallelism: 1,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_hash_and_verify() {
+        let hasher = cheap_hasher(1);
+        let hash = hasher.hash("hunter22").await.unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(hasher.verify("hunter22", &hash).await.unwrap());
+        assert!(!hasher.verify("hunter23", &hash).await.unwrap());
+        assert!(!hasher.needs_rehash(&hash));
+    }
+
+    #[tokio::test]
+    async fn test_verifies_legacy_md5_crypt() {
+        let hasher = cheap_hasher(1);
+
+        // As made by `crypt('hunter22', gen_salt('md5'))`
+        let hash = "$1$9rXa6FzM$oMWcFjg5QpZQ7X.t5nNtA/";
+
+        assert!(hasher.verify("hunter22", hash).await.unwrap());
+        assert!(!hasher.verify("hunter23", hash).await.unwrap());
+        assert!(hasher.needs_rehash(hash));
+    }
+
+    #[tokio::test]
+    async fn test_verify_missing_is_false() {
+        let hasher = cheap_hasher(1);
+        assert!(!hasher.verify_missing("").await.unwrap());
+        assert!(!hasher.needs_rehash(&hasher.dummy_hash));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_hash_is_an_error() {
+        let hasher = cheap_hasher(1);
+        assert!(hasher.verify("hunter22", "not a hash").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_changed_cost_needs_rehash() {
+        let hash = cheap_hasher(1).hash("hunter22").await.unwrap();
+        assert!(cheap_hasher(2).needs_rehash(&hash));
+    }
+}
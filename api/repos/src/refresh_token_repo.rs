@@ -1,9 +1,22 @@
+//! Refresh Token Repository
+//!
+//! Only SHA-256 hashes of refresh tokens are stored, so a leaked
+//! table can't be used to refresh sessions. Tokens are random, so
+//! they need no salt or slow hash, and can be looked up by hash.
+
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
 use sqlx::{Error as SqlxError, PgPool};
+use uuid::Uuid;
 
 use models::RefreshToken;
 
+/// Hash a refresh token for storage.
+pub(crate) fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
 #[derive(Clone)]
 pub struct RefreshTokenRepo {
     db: PgPool,
@@ -17,10 +30,22 @@ impl RefreshTokenRepo {
 
 #[async_trait]
 pub trait RefreshTokenRepoOps: Send + Sync {
+    /// Save the first refresh token of a new family, along with the
+    /// client it was issued to.
     async fn create_token(
         &self,
         username: &str,
         token: &str,
+        user_agent: Option<&str>,
+        client_ip: Option<&str>,
+    ) -> Result<RefreshToken, SqlxError>;
+
+    /// Save the token replacing `previous`, in the same family and
+    /// for the same client, keeping when the family started.
+    async fn rotate_token(
+        &self,
+        previous: &RefreshToken,
+        token: &str,
     ) -> Result<RefreshToken, SqlxError>;
 
     async fn find_by_token(
@@ -28,13 +53,41 @@ pub trait RefreshTokenRepoOps: Send + Sync {
         token: &str,
     ) -> Result<Option<RefreshToken>, SqlxError>;
 
+    /// Get a user's unused and unexpired tokens, most recently
+    /// used first.
+    async fn find_active_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Vec<RefreshToken>, SqlxError>;
+
+    /// Delete every token of a family.
+    async fn delete_family(&self, family_id: Uuid) -> Result<(), SqlxError>;
+
+    /// Delete every token of one of a user's families, returning
+    /// whether it existed.
+    async fn delete_user_family(
+        &self,
+        username: &str,
+        family_id: Uuid,
+    ) -> Result<bool, SqlxError>;
+
     async fn update_last_used(&self, token: &str) -> Result<(), SqlxError>;
 
     async fn delete_token(&self, token: &str) -> Result<(), SqlxError>;
 
     async fn delete_all_user_tokens(&self, username: &str) -> Result<(), SqlxError>;
 
-    async fn mark_token_used(&self, token: &str) -> Result<(), SqlxError>;
+    /// Mark a token used, returning whether it wasn't already, so
+    /// that only one of concurrent refreshes with it succeeds.
+    async fn mark_token_used(&self, token: &str) -> Result<bool, SqlxError>;
+
+    /// Delete up to `limit` tokens that have expired or were used
+    /// before `used_before`, returning how many were deleted.
+    async fn purge_tokens(
+        &self,
+        used_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<u64, SqlxError>;
 }
 
 #[async_trait]
@@ -43,17 +96,56 @@ impl RefreshTokenRepoOps for RefreshTokenRepo {
         &self,
         username: &str,
         token: &str,
+        user_agent: Option<&str>,
+        client_ip: Option<&str>,
     ) -> Result<RefreshToken, SqlxError> {
+        // A family is identified by its first token
+        let id = Uuid::now_v7();
+
         let refresh_token = sqlx::query_as::<_, RefreshToken>(
             r#"
-            INSERT INTO refresh_tokens (username, token)
-            VALUES ($1, $2)
-            RETURNING id, username, token, expires_at, is_used,
-                used_at, created_at, last_used_at
+            INSERT INTO refresh_tokens
+                (id, family_id, username, token_hash, user_agent, client_ip)
+            VALUES ($1, $1, $2, $3, $4, $5)
+            RETURNING id, username, token_hash, expires_at, is_used,
+                used_at, created_at, last_used_at, family_id, user_agent, client_ip,
+                session_created_at
             "#,
         )
+        .bind(id)
         .bind(username)
-        .bind(token)
+        .bind(hash_token(token))
+        .bind(user_agent)
+        .bind(client_ip)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(refresh_token)
+    }
+
+    async fn rotate_token(
+        &self,
+        previous: &RefreshToken,
+        token: &str,
+    ) -> Result<RefreshToken, SqlxError> {
+        let refresh_token = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            INSERT INTO refresh_tokens
+                (id, family_id, username, token_hash, user_agent, client_ip,
+                session_created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, username, token_hash, expires_at, is_used,
+                used_at, created_at, last_used_at, family_id, user_agent, client_ip,
+                session_created_at
+            "#,
+        )
+        .bind(Uuid::now_v7())
+        .bind(previous.family_id)
+        .bind(&previous.username)
+        .bind(hash_token(token))
+        .bind(&previous.user_agent)
+        .bind(&previous.client_ip)
+        .bind(previous.session_created_at)
         .fetch_one(&self.db)
         .await?;
 
@@ -66,23 +158,72 @@ impl RefreshTokenRepoOps for RefreshTokenRepo {
     ) -> Result<Option<RefreshToken>, SqlxError> {
         let refresh_token = sqlx::query_as::<_, RefreshToken>(
             r#"
-            SELECT id, username, token, expires_at, is_used,
-                used_at, created_at, last_used_at
-            FROM refresh_tokens WHERE token = $1
+            SELECT id, username, token_hash, expires_at, is_used,
+                used_at, created_at, last_used_at, family_id, user_agent, client_ip,
+                session_created_at
+            FROM refresh_tokens WHERE token_hash = $1
             "#,
         )
-        .bind(token)
+        .bind(hash_token(token))
         .fetch_optional(&self.db)
         .await?;
 
         Ok(refresh_token)
     }
 
+    async fn find_active_by_username(
+        &self,
+        username: &str,
+    ) -> Result<Vec<RefreshToken>, SqlxError> {
+        let refresh_tokens = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            SELECT id, username, token_hash, expires_at, is_used,
+                used_at, created_at, last_used_at, family_id, user_agent, client_ip,
+                session_created_at
+            FROM refresh_tokens
+            WHERE username = $1 AND NOT is_used AND expires_at > NOW()
+            ORDER BY last_used_at DESC
+            "#,
+        )
+        .bind(username)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(refresh_tokens)
+    }
+
+    async fn delete_family(&self, family_id: Uuid) -> Result<(), SqlxError> {
+        sqlx::query!(
+            "DELETE FROM refresh_tokens WHERE family_id = $1",
+            family_id,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_user_family(
+        &self,
+        username: &str,
+        family_id: Uuid,
+    ) -> Result<bool, SqlxError> {
+        let result = sqlx::query!(
+            "DELETE FROM refresh_tokens WHERE username = $1 AND family_id = $2",
+            username,
+            family_id,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     async fn update_last_used(&self, token: &str) -> Result<(), SqlxError> {
         sqlx::query!(
-            "UPDATE refresh_tokens SET last_used_at = $1 WHERE token = $2",
+            "UPDATE refresh_tokens SET last_used_at = $1 WHERE token_hash = $2",
             Utc::now(),
-            token,
+            hash_token(token),
         )
         .execute(&self.db)
         .await?;
@@ -92,8 +233,8 @@ impl RefreshTokenRepoOps for RefreshTokenRepo {
 
     async fn delete_token(&self, token: &str) -> Result<(), SqlxError> {
         sqlx::query!(
-            "DELETE FROM refresh_tokens WHERE token = $1",
-            token,
+            "DELETE FROM refresh_tokens WHERE token_hash = $1",
+            hash_token(token),
         )
         .execute(&self.db)
         .await?;
@@ -112,19 +253,56 @@ impl RefreshTokenRepoOps for RefreshTokenRepo {
         Ok(())
     }
 
-    async fn mark_token_used(&self, token: &str) -> Result<(), SqlxError> {
-        sqlx::query!(
+    async fn mark_token_used(&self, token: &str) -> Result<bool, SqlxError> {
+        let result = sqlx::query!(
             r#"
             UPDATE refresh_tokens
             SET is_used = TRUE, used_at = $1
-            WHERE token = $2
+            WHERE token_hash = $2 AND NOT is_used
             "#,
             Utc::now(),
-            token,
+            hash_token(token),
         )
         .execute(&self.db)
         .await?;
 
-        Ok(())
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn purge_tokens(
+        &self,
+        used_before: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<u64, SqlxError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM refresh_tokens WHERE id IN (
+                SELECT id FROM refresh_tokens
+                WHERE expires_at < NOW() OR (is_used AND used_at < $1)
+                LIMIT $2
+            )
+            "#,
+            used_before,
+            limit,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_token_matches_postgres_sha256() {
+        // As given by `encode(sha256('abc'), 'hex')`, which migrates
+        // existing tokens
+        assert_eq!(
+            hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        );
     }
 }
@@ -0,0 +1,147 @@
+use async_trait::async_trait;
+use sqlx::{Error as SqlxError, PgPool};
+
+use models::TotpCredential;
+
+#[derive(Clone)]
+pub struct TotpRepo {
+    db: PgPool,
+}
+
+impl TotpRepo {
+    pub fn new(db: PgPool) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+pub trait TotpRepoOps: Send + Sync {
+    async fn find(&self, username: &str) -> Result<Option<TotpCredential>, SqlxError>;
+
+    /// Store a new secret awaiting verification, replacing any
+    /// earlier one that wasn't verified. Enabled secrets are kept.
+    async fn save_pending(&self, username: &str, secret: &str) -> Result<(), SqlxError>;
+
+    /// Enable a verified secret, replacing the user's recovery codes.
+    async fn enable(
+        &self,
+        username: &str,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), SqlxError>;
+
+    /// Record that a code was accepted for a time step, returning
+    /// false if one already was for the same or a later step.
+    async fn use_step(&self, username: &str, step: i64) -> Result<bool, SqlxError>;
+
+    /// Use up a recovery code, returning false if it doesn't exist
+    /// or was already used.
+    async fn use_recovery_code(&self, username: &str, code_hash: &str) -> Result<bool, SqlxError>;
+}
+
+#[async_trait]
+impl TotpRepoOps for TotpRepo {
+    async fn find(&self, username: &str) -> Result<Option<TotpCredential>, SqlxError> {
+        let credential = sqlx::query_as::<_, TotpCredential>(
+            r#"
+            SELECT username, secret, enabled, last_used_step
+            FROM totp_credentials WHERE username = $1
+            "#,
+        )
+        .bind(username)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(credential)
+    }
+
+    async fn save_pending(&self, username: &str, secret: &str) -> Result<(), SqlxError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO totp_credentials (username, secret)
+            VALUES ($1, $2)
+            ON CONFLICT (username) DO UPDATE
+            SET secret = $2, last_used_step = NULL, created_at = NOW()
+            WHERE totp_credentials.enabled = FALSE
+            "#,
+            username,
+            secret,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn enable(
+        &self,
+        username: &str,
+        step: i64,
+        recovery_code_hashes: &[String],
+    ) -> Result<(), SqlxError> {
+        let mut tx = self.db.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE totp_credentials
+            SET enabled = TRUE, last_used_step = $2
+            WHERE username = $1
+            "#,
+            username,
+            step,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM recovery_codes WHERE username = $1",
+            username,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (username, code_hash)
+            SELECT $1, unnest($2::text[])
+            "#,
+            username,
+            recovery_code_hashes,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+
+    async fn use_step(&self, username: &str, step: i64) -> Result<bool, SqlxError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_credentials SET last_used_step = $2
+            WHERE username = $1
+                AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            username,
+            step,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn use_recovery_code(&self, username: &str, code_hash: &str) -> Result<bool, SqlxError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE recovery_codes SET used_at = NOW()
+            WHERE username = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+            username,
+            code_hash,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
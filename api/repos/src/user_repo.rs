@@ -1,70 +1,157 @@
 use async_trait::async_trait;
 use sqlx::{Error as SqlxError, PgPool};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use tracing::warn;
 use uuid::Uuid;
 
 use models::{User, UserInfo};
 
-/// Result returning sqlx::Error on errors.
-type Result<T> = anyhow::Result<T, SqlxError>;
+use crate::{PasswordHashError, PasswordHasher};
+
+/// Result returning UserRepoError on errors.
+type Result<T> = anyhow::Result<T, UserRepoError>;
+
+#[derive(Debug)]
+pub enum UserRepoError {
+    /// The username is in use, in any case
+    UsernameTaken,
+
+    /// A password couldn't be hashed, or checked against its
+    /// stored hash
+    PasswordHash(PasswordHashError),
+    Query(SqlxError),
+}
+
+impl From<PasswordHashError> for UserRepoError {
+    fn from(value: PasswordHashError) -> Self {
+        UserRepoError::PasswordHash(value)
+    }
+}
+
+impl From<SqlxError> for UserRepoError {
+    fn from(value: SqlxError) -> Self {
+        UserRepoError::Query(value)
+    }
+}
+
+impl Display for UserRepoError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            UserRepoError::UsernameTaken => write!(f, "Username already in use"),
+            UserRepoError::PasswordHash(e) => write!(f, "{}", e),
+            UserRepoError::Query(e) => write!(f, "User query failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for UserRepoError {}
 
 #[derive(Clone)]
 pub struct UserRepo {
     db: PgPool,
+    hasher: PasswordHasher,
 }
 
 impl UserRepo {
-    pub fn new(db: PgPool) -> Self {
-        Self { db }
+    pub fn new(db: PgPool, hasher: PasswordHasher) -> Self {
+        Self { db, hasher }
+    }
+
+    /// Replace a verified password hash with one made with the
+    /// current hashing parameters.
+    async fn rehash(&self, user: &User, old_hash: &str) -> Result<()> {
+        let new_hash = self.hasher.hash(&user.password).await?;
+
+        // Only replace the hash that was verified, in case the
+        // password was changed in the meantime
+        sqlx::query!(
+            r#"
+            UPDATE user_profile SET password = $3
+            WHERE username = $1 AND password = $2
+            "#,
+            &user.username,
+            old_hash,
+            new_hash,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
     }
 }
 
 #[async_trait]
 pub trait UserRepoOps: Send + Sync {
+    /// Create a user, failing with `UsernameTaken` if the username
+    /// is in use in any case.
     async fn create(&self, user: &User) -> Result<UserInfo>;
 
     async fn authorize(&self, user: &User) -> Result<bool>;
 
     async fn find(&self, username: &str) -> Result<Option<UserInfo>>;
+
+    /// Whether a username is in use, ignoring case.
+    async fn username_taken(&self, username: &str) -> Result<bool>;
+
+    async fn update_password(&self, username: &str, password: &str) -> Result<()>;
+
+    async fn delete(&self, username: &str) -> Result<()>;
 }
 
 #[async_trait]
 impl UserRepoOps for UserRepo {
     async fn create(&self, user: &User) -> Result<UserInfo> {
         let object_base_path = Uuid::now_v7().to_string();
+        let password_hash = self.hasher.hash(&user.password).await?;
 
         let user_info = sqlx::query_as::<_, UserInfo>(
             r#"
             INSERT INTO user_profile (username, password, object_base_path)
-            VALUES ($1, crypt($2, gen_salt('md5')), $3)
+            VALUES ($1, $2, $3)
             RETURNING username, object_base_path
             "#,
         )
         .bind(&user.username)
-        .bind(&user.password)
+        .bind(&password_hash)
         .bind(&object_base_path)
         .fetch_one(&self.db)
-        .await?;
+        .await
+        .map_err(|e| match e {
+            // Another registration took the username, in some case,
+            // since it was checked
+            SqlxError::Database(ref db_err) if db_err.is_unique_violation() => {
+                UserRepoError::UsernameTaken
+            }
+            e => UserRepoError::Query(e),
+        })?;
 
         Ok(user_info)
     }
 
     async fn authorize(&self, user: &User) -> Result<bool> {
-        match sqlx::query!(
-            r#"
-            SELECT (password = crypt($2, password)) AS is_match
-            FROM user_profile WHERE username = $1
-            "#,
+        let password_hash = sqlx::query_scalar!(
+            "SELECT password FROM user_profile WHERE username = $1",
             &user.username,
-            &user.password,
         )
-        .fetch_one(&self.db)
-        .await {
-            Ok(result) => Ok(result.is_match.unwrap_or(false)),
-            Err(e) => match e {
-                SqlxError::RowNotFound => Ok(false),
-                _ => Err(e),
+        .fetch_optional(&self.db)
+        .await?;
+
+        let Some(password_hash) = password_hash else {
+            return Ok(self.hasher.verify_missing(&user.password).await?);
+        };
+
+        if !self.hasher.verify(&user.password, &password_hash).await? {
+            return Ok(false);
+        }
+
+        if self.hasher.needs_rehash(&password_hash) {
+            // Upgrading is best effort; the login itself succeeded
+            if let Err(e) = self.rehash(user, &password_hash).await {
+                warn!("Failed to rehash password for {}: {}", user.username, e);
             }
         }
+
+        Ok(true)
     }
 
     async fn find(
@@ -83,4 +170,40 @@ impl UserRepoOps for UserRepo {
 
         Ok(user_info)
     }
+
+    async fn username_taken(&self, username: &str) -> Result<bool> {
+        let is_taken = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM user_profile WHERE lower(username) = lower($1)
+            ) AS "is_taken!"
+            "#,
+            username,
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(is_taken)
+    }
+
+    async fn update_password(&self, username: &str, password: &str) -> Result<()> {
+        let password_hash = self.hasher.hash(password).await?;
+
+        sqlx::query!(
+            "UPDATE user_profile SET password = $2 WHERE username = $1",
+            username,
+            password_hash,
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Delete a user's profile. Their image rows and refresh tokens
+    /// are deleted by foreign key cascades; objects in the store
+    /// aren't, so `ImageRepoOps::delete_all` must be called first.
+    async fn delete(&self, username: &str) -> Result<()> {
+        Ok(db::delete_user(&self.db, username).await?)
+    }
 }
@@ -6,6 +6,12 @@ use std::fmt::{Display, Formatter, Result as FmtResult};
 #[derive(Debug)]
 pub struct S3Error(String);
 
+impl S3Error {
+    pub fn new(message: impl Into<String>) -> Self {
+        S3Error(message.into())
+    }
+}
+
 impl<T: ProvideErrorMetadata> From<T> for S3Error {
     fn from(value: T) -> Self {
         let err_code = value
@@ -1,11 +1,14 @@
 use aws_sdk_s3::{
     operation::{
         delete_object::DeleteObjectOutput,
+        delete_objects::DeleteObjectsOutput,
         get_object::GetObjectOutput,
+        list_object_versions::ListObjectVersionsOutput,
         list_objects_v2::ListObjectsV2Output,
         put_object::PutObjectOutput,
     },
     primitives::ByteStream,
+    types::{Delete, ObjectIdentifier},
     Client,
 };
 use bytes::Bytes;
@@ -35,34 +38,38 @@ pub async fn upload_object(
     Ok(result)
 }
 
-/// Retrieve specific version of object from S3 bucket.
+/// Retrieve specific version of object from S3 bucket, or its
+/// latest version if no version id is given.
 pub async fn get_object(
     client: &Client,
     object_key: &str,
-    version_id: &str,
+    version_id: Option<&str>,
 ) -> Result<GetObjectOutput> {
     let bucket_name = get_bucket_name().await;
     let object = client
         .get_object()
         .bucket(bucket_name)
         .key(object_key.to_string())
-        .version_id(version_id.to_string())
+        .set_version_id(version_id.map(String::from))
         .send()
         .await?;
 
     Ok(object)
 }
 
-/// Retrieve all objects from S3 bucket.
+/// Retrieve a page of the objects in S3 bucket whose keys start
+/// with a prefix, continuing from the given continuation token.
 pub async fn get_objects(
     client: &Client,
     prefix: &str,
+    continuation_token: Option<String>,
 ) -> Result<ListObjectsV2Output> {
     let bucket_name = get_bucket_name().await;
     let objects = client
         .list_objects_v2()
         .bucket(bucket_name)
         .prefix(prefix)
+        .set_continuation_token(continuation_token)
         .send()
         .await?;
 
@@ -85,6 +92,72 @@ pub async fn delete_object(
     Ok(object)
 }
 
+/// Retrieve a page of every version and delete marker of the
+/// objects in S3 bucket whose keys start with a prefix, starting
+/// after the given markers.
+pub async fn get_object_versions(
+    client: &Client,
+    prefix: &str,
+    key_marker: Option<String>,
+    version_id_marker: Option<String>,
+) -> Result<ListObjectVersionsOutput> {
+    let bucket_name = get_bucket_name().await;
+    let versions = client
+        .list_object_versions()
+        .bucket(bucket_name)
+        .prefix(prefix)
+        .set_key_marker(key_marker)
+        .set_version_id_marker(version_id_marker)
+        .send()
+        .await?;
+
+    Ok(versions)
+}
+
+/// Permanently delete specific versions of objects from S3 bucket,
+/// given as pairs of object key and version id. At most 1000
+/// versions can be deleted at once.
+pub async fn delete_object_versions(
+    client: &Client,
+    versions: &[(String, String)],
+) -> Result<DeleteObjectsOutput> {
+    let objects = versions
+        .iter()
+        .map(|(key, version_id)| {
+            ObjectIdentifier::builder()
+                .key(key)
+                .version_id(version_id)
+                .build()
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| S3Error::new(e.to_string()))?;
+
+    let delete = Delete::builder()
+        .set_objects(Some(objects))
+        .quiet(true)
+        .build()
+        .map_err(|e| S3Error::new(e.to_string()))?;
+
+    let bucket_name = get_bucket_name().await;
+    let output = client
+        .delete_objects()
+        .bucket(bucket_name)
+        .delete(delete)
+        .send()
+        .await?;
+
+    // Objects are deleted one by one, so some may fail on their own
+    if let Some(e) = output.errors().first() {
+        return Err(S3Error::new(format!(
+            "Failed to delete {}: {}",
+            e.key().unwrap_or("?"),
+            e.message().unwrap_or("?"),
+        )));
+    }
+
+    Ok(output)
+}
+
 async fn get_bucket_name() -> String {
     match config::get_s3_bucket_name().await {
         Ok(name) => name,
@@ -1,4 +1,4 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use models::UserInfo;
 
@@ -9,7 +9,62 @@ pub struct LoginResponse {
     pub refresh_token: String,
 }
 
+/// Response to a correct password from a user with two-factor
+/// authentication, whose challenge token must be exchanged for
+/// tokens at `/login/totp`.
+#[derive(Serialize)]
+pub struct MfaChallengeResponse {
+    pub mfa_required: bool,
+    pub challenge_token: String,
+}
+
+/// Second login step, with either a TOTP code or a recovery code.
+#[derive(Deserialize)]
+pub struct TotpLoginRequest {
+    pub challenge_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct UserResponse {
     pub user: UserInfo,
 }
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize)]
+pub struct ChangePasswordResponse {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteUserRequest {
+    pub password: String,
+}
+
+#[derive(Serialize)]
+pub struct DeleteUserResponse {
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct TotpEnrollmentResponse {
+    /// Base32-encoded secret, for entering by hand
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
@@ -1,10 +1,27 @@
 use serde::{Deserialize, Serialize};
 
+use models::{Operation, OutputFormat, ResizeFit};
+
 #[derive(Deserialize)]
 pub struct ImageRenameRequest {
     pub image_name: String,
 }
 
+#[derive(Deserialize)]
+pub struct TransformRequest {
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Deserialize)]
+pub struct TransformParams {
+    /// Return the rendered image instead of saving a new version
+    #[serde(default)]
+    pub preview: bool,
+
+    /// Longest edge, in pixels, of a rendered preview
+    pub max_size: Option<u32>,
+}
+
 #[derive(Deserialize)]
 pub struct PaginationParams {
     #[serde(default = "default_page")]
@@ -17,6 +34,29 @@ pub struct PaginationParams {
 fn default_page() -> u32 { 1 }
 fn default_limit() -> u32 { 10 }
 
+#[derive(Deserialize)]
+pub struct RenditionParams {
+    /// Width to resize to
+    pub w: Option<u32>,
+
+    /// Height to resize to
+    pub h: Option<u32>,
+
+    #[serde(default)]
+    pub fit: ResizeFit,
+
+    /// Format to convert to
+    pub format: Option<OutputFormat>,
+}
+
+#[derive(Deserialize)]
+pub struct ThumbnailParams {
+    #[serde(default = "default_thumbnail_size")]
+    pub size: u32,
+}
+
+fn default_thumbnail_size() -> u32 { 256 }
+
 #[derive(Serialize)]
 pub struct ImageUpdateResponse {
     pub updated: bool,
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use models::{AuditEvent, Session};
+
 #[derive(Deserialize)]
 pub struct RefreshTokenRequest {
     pub refresh_token: String,
@@ -15,3 +17,12 @@ pub struct RefreshTokenResponse {
 pub struct LogoutResponse {
     pub message: String,
 }
+
+#[derive(Serialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<Session>,
+
+    /// Recent security events of the user, such as sessions revoked
+    /// for refresh token reuse, newest first
+    pub security_events: Vec<AuditEvent>,
+}
@@ -1,23 +1,21 @@
-use anyhow::Result;
-use aws_sdk_s3::Client as S3Client;
-use sqlx::PgPool;
+use anyhow::{anyhow, Result};
 use std::sync::Arc;
 
-use db;
 use repos::{
     RefreshTokenRepo, RefreshTokenRepoOps,
     UserRepo, UserRepoOps,
     ImageRepo, ImageRepoOps,
+    LoginAttemptRepo, LoginAttemptRepoOps,
+    AuditLogRepo, AuditLogRepoOps,
+    TotpRepo, TotpRepoOps,
+    PasswordHasher,
 };
-use s3;
+use storage::ObjectStore;
 
 #[derive(Clone)]
 pub struct AppState {
-    /// PostgreSQL connection pool
-    pub db: PgPool,
-
-    /// AWS S3 client
-    pub img_store_client: S3Client,
+    /// Image object store (S3 or local filesystem)
+    pub object_store: Arc<dyn ObjectStore>,
 
     /// Refresh token repository
     pub refresh_token_repo: Arc<dyn RefreshTokenRepoOps>,
@@ -27,30 +25,146 @@ pub struct AppState {
 
     /// Image repository
     pub image_repo: Arc<dyn ImageRepoOps>,
+
+    /// Failed login attempt repository
+    pub login_attempt_repo: Arc<dyn LoginAttemptRepoOps>,
+
+    /// Security audit log
+    pub audit_log_repo: Arc<dyn AuditLogRepoOps>,
+
+    /// TOTP secret and recovery code repository
+    pub totp_repo: Arc<dyn TotpRepoOps>,
 }
 
 impl AppState {
+    /// Make the application state using the configured
+    /// database and object store.
     pub async fn new() -> Result<Self> {
         let db = db::create_conn_pool().await?;
+        let object_store = storage::get_object_store().await?;
+        let hasher = PasswordHasher::new(&config::get_password_hash_config().await?)?;
+
+        let state = Self::builder()
+            .refresh_token_repo(Arc::new(RefreshTokenRepo::new(db.clone())))
+            .user_repo(Arc::new(UserRepo::new(db.clone(), hasher)))
+            .login_attempt_repo(Arc::new(LoginAttemptRepo::new(db.clone())))
+            .audit_log_repo(Arc::new(AuditLogRepo::new(db.clone())))
+            .totp_repo(Arc::new(TotpRepo::new(db.clone())))
+            .image_repo(Arc::new(ImageRepo::new(db, object_store.clone())))
+            .object_store(object_store)
+            .build()?;
 
-        let refresh_token_repo: Arc<dyn RefreshTokenRepoOps> = Arc::new(
-            RefreshTokenRepo::new(db.clone()),
-        );
-        let user_repo: Arc<dyn UserRepoOps> = Arc::new(
-            UserRepo::new(db.clone()),
-        );
-
-        let img_store_client = s3::get_client().await?;
-        let image_repo: Arc<dyn ImageRepoOps> = Arc::new(
-            ImageRepo::new(db.clone(), img_store_client.clone()),
-        );
-
-        Ok(Self {
-            db,
-            img_store_client,
-            refresh_token_repo,
-            user_repo,
-            image_repo,
+        Ok(state)
+    }
+
+    /// Start building application state from injected parts.
+    pub fn builder() -> AppStateBuilder {
+        AppStateBuilder::default()
+    }
+}
+
+/// Builder for application state. Every part must be given, unless
+/// the `test-util` feature is on, in which case any part that isn't
+/// is kept in memory so an unconfigured builder makes test state.
+#[derive(Default)]
+pub struct AppStateBuilder {
+    object_store: Option<Arc<dyn ObjectStore>>,
+    refresh_token_repo: Option<Arc<dyn RefreshTokenRepoOps>>,
+    user_repo: Option<Arc<dyn UserRepoOps>>,
+    image_repo: Option<Arc<dyn ImageRepoOps>>,
+    login_attempt_repo: Option<Arc<dyn LoginAttemptRepoOps>>,
+    audit_log_repo: Option<Arc<dyn AuditLogRepoOps>>,
+    totp_repo: Option<Arc<dyn TotpRepoOps>>,
+}
+
+impl AppStateBuilder {
+    pub fn object_store(mut self, object_store: Arc<dyn ObjectStore>) -> Self {
+        self.object_store = Some(object_store);
+        self
+    }
+
+    pub fn refresh_token_repo(mut self, repo: Arc<dyn RefreshTokenRepoOps>) -> Self {
+        self.refresh_token_repo = Some(repo);
+        self
+    }
+
+    pub fn user_repo(mut self, repo: Arc<dyn UserRepoOps>) -> Self {
+        self.user_repo = Some(repo);
+        self
+    }
+
+    pub fn image_repo(mut self, repo: Arc<dyn ImageRepoOps>) -> Self {
+        self.image_repo = Some(repo);
+        self
+    }
+
+    pub fn login_attempt_repo(mut self, repo: Arc<dyn LoginAttemptRepoOps>) -> Self {
+        self.login_attempt_repo = Some(repo);
+        self
+    }
+
+    pub fn audit_log_repo(mut self, repo: Arc<dyn AuditLogRepoOps>) -> Self {
+        self.audit_log_repo = Some(repo);
+        self
+    }
+
+    pub fn totp_repo(mut self, repo: Arc<dyn TotpRepoOps>) -> Self {
+        self.totp_repo = Some(repo);
+        self
+    }
+
+    pub fn build(self) -> Result<AppState> {
+        #[cfg(feature = "test-util")]
+        let this = self.in_memory_defaults();
+        #[cfg(not(feature = "test-util"))]
+        let this = self;
+
+        let missing = |part: &str| anyhow!("Application state is missing its {}", part);
+
+        Ok(AppState {
+            object_store: this.object_store.ok_or_else(|| missing("object store"))?,
+            refresh_token_repo: this
+                .refresh_token_repo
+                .ok_or_else(|| missing("refresh token repository"))?,
+            user_repo: this.user_repo.ok_or_else(|| missing("user repository"))?,
+            image_repo: this.image_repo.ok_or_else(|| missing("image repository"))?,
+            login_attempt_repo: this
+                .login_attempt_repo
+                .ok_or_else(|| missing("login attempt repository"))?,
+            audit_log_repo: this
+                .audit_log_repo
+                .ok_or_else(|| missing("audit log repository"))?,
+            totp_repo: this.totp_repo.ok_or_else(|| missing("TOTP repository"))?,
         })
     }
+
+    /// Fill every part that wasn't given with an in-memory one.
+    #[cfg(feature = "test-util")]
+    fn in_memory_defaults(mut self) -> Self {
+        use repos::{
+            InMemoryAuditLogRepo, InMemoryLoginAttemptRepo, InMemoryRefreshTokenRepo,
+            InMemoryTotpRepo, InMemoryUserRepo,
+        };
+        use storage::MemoryStore;
+
+        let object_store = self
+            .object_store
+            .get_or_insert_with(|| Arc::new(MemoryStore::new()))
+            .clone();
+
+        self.refresh_token_repo
+            .get_or_insert_with(|| Arc::new(InMemoryRefreshTokenRepo::new()));
+        self.user_repo
+            .get_or_insert_with(|| Arc::new(InMemoryUserRepo::new()));
+        self.image_repo
+            .get_or_insert_with(|| Arc::new(ImageRepo::in_memory(object_store)));
+        self.login_attempt_repo
+            .get_or_insert_with(|| Arc::new(InMemoryLoginAttemptRepo::new()));
+        self.audit_log_repo
+            .get_or_insert_with(|| Arc::new(InMemoryAuditLogRepo::new()));
+        self.totp_repo
+            .get_or_insert_with(|| Arc::new(InMemoryTotpRepo::new()));
+
+        self
+    }
 }
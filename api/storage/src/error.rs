@@ -0,0 +1,33 @@
+//! Object Storage Errors
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io;
+
+use s3::error::S3Error;
+
+#[derive(Debug)]
+pub struct StorageError(String);
+
+impl StorageError {
+    pub fn new(message: impl Into<String>) -> Self {
+        StorageError(message.into())
+    }
+}
+
+impl From<S3Error> for StorageError {
+    fn from(value: S3Error) -> Self {
+        StorageError(value.to_string())
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(value: io::Error) -> Self {
+        StorageError(value.to_string())
+    }
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.0)
+    }
+}
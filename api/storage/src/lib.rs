@@ -0,0 +1,71 @@
+//! Object Storage
+//!
+//! Images are stored as versioned objects. In production the store
+//! is an S3 bucket; for offline development it can be a directory
+//! on the local filesystem instead, and tests can keep it in memory.
+
+use anyhow::Result as AnyResult;
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use config::StorageConfig;
+
+pub mod error;
+pub mod local;
+#[cfg(feature = "test-util")]
+pub mod memory;
+pub mod s3_store;
+
+pub use error::StorageError;
+pub use local::LocalStore;
+#[cfg(feature = "test-util")]
+pub use memory::MemoryStore;
+pub use s3_store::S3Store;
+
+pub type Result<T> = anyhow::Result<T, StorageError>;
+
+/// Summary of a stored object's latest version.
+#[derive(Clone, Debug)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Store data as a new version of an object, returning the
+    /// version id if the store keeps versions.
+    async fn put(&self, key: &str, data: Bytes) -> Result<Option<String>>;
+
+    /// Read a specific version of an object, or its latest
+    /// version if no version id is given.
+    async fn get(&self, key: &str, version_id: Option<&str>) -> Result<Bytes>;
+
+    /// List the objects whose keys start with the given prefix.
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>>;
+
+    /// Delete an object.
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Permanently delete every object whose key starts with the
+    /// given prefix, including all of their versions.
+    async fn delete_all(&self, prefix: &str) -> Result<()> {
+        for object in self.list(prefix).await? {
+            self.delete(&object.key).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Make the object store selected by the server's configuration.
+pub async fn get_object_store() -> AnyResult<Arc<dyn ObjectStore>> {
+    let store: Arc<dyn ObjectStore> = match config::get_storage_config().await? {
+        StorageConfig::S3 => Arc::new(S3Store::new(s3::get_client().await?)),
+        StorageConfig::Local { root } => Arc::new(LocalStore::new(root)),
+    };
+
+    Ok(store)
+}
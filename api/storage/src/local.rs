@@ -0,0 +1,225 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+use uuid::Uuid;
+
+use super::{ObjectInfo, ObjectStore, Result, StorageError};
+
+/// Suffix of the directory holding an object's versions
+const VERSIONS_SUFFIX: &str = ".versions";
+
+/// Object store backed by a directory on the local filesystem.
+///
+/// S3 versioning is emulated by storing each object as a directory
+/// of version files, named with time-ordered UUIDs so that the
+/// latest version sorts last.
+#[derive(Clone)]
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Get the directory holding an object's versions, rejecting
+    /// keys that would escape the store's root directory.
+    fn versions_dir(&self, key: &str) -> Result<PathBuf> {
+        let path = Path::new(key);
+        let is_valid = !key.is_empty()
+            && !key.ends_with('/')
+            && path.components().all(|c| matches!(c, Component::Normal(_)));
+
+        if !is_valid {
+            return Err(StorageError::new(format!("Invalid object key: {}", key)));
+        }
+
+        Ok(self.root.join(format!("{}{}", key, VERSIONS_SUFFIX)))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for LocalStore {
+    async fn put(&self, key: &str, data: Bytes) -> Result<Option<String>> {
+        let dir = self.versions_dir(key)?;
+        fs::create_dir_all(&dir).await?;
+
+        // Write to a temporary file first so that readers never
+        // see a partially written version
+        let version_id = Uuid::now_v7().simple().to_string();
+        let temp_path = dir.join(format!(".{}", version_id));
+        fs::write(&temp_path, &data).await?;
+        fs::rename(&temp_path, dir.join(&version_id)).await?;
+
+        Ok(Some(version_id))
+    }
+
+    async fn get(&self, key: &str, version_id: Option<&str>) -> Result<Bytes> {
+        let dir = self.versions_dir(key)?;
+
+        let version_id = match version_id {
+            Some(id) if is_version_id(id) => id.to_string(),
+            Some(id) => {
+                return Err(StorageError::new(format!("Invalid version id: {}", id)));
+            }
+            None => latest_version(&dir)
+                .await?
+                .ok_or_else(|| StorageError::new(format!("No such key: {}", key)))?,
+        };
+
+        let data = fs::read(dir.join(version_id)).await?;
+        Ok(Bytes::from(data))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        let mut objects = Vec::new();
+        let mut pending = vec![self.root.clone()];
+
+        while let Some(dir) = pending.pop() {
+            let mut entries = match fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                if !entry.file_type().await?.is_dir() {
+                    continue;
+                }
+
+                let path = entry.path();
+                let Some(key) = object_key(&self.root, &path) else {
+                    pending.push(path);
+                    continue;
+                };
+
+                if !key.starts_with(prefix) {
+                    continue;
+                }
+
+                // Skip objects whose only version is still being written
+                if let Some(version_id) = latest_version(&path).await? {
+                    let modified = fs::metadata(path.join(version_id))
+                        .await?
+                        .modified()?;
+
+                    objects.push(ObjectInfo {
+                        key,
+                        last_modified: Some(DateTime::<Utc>::from(modified)),
+                    });
+                }
+            }
+        }
+
+        Ok(objects)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let dir = self.versions_dir(key)?;
+
+        match fs::remove_dir_all(&dir).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Get the key of the object stored in a directory, if it's
+/// a versions directory.
+fn object_key(root: &Path, dir: &Path) -> Option<String> {
+    let relative = dir.strip_prefix(root).ok()?.to_str()?;
+    let key = relative.strip_suffix(VERSIONS_SUFFIX)?;
+
+    Some(key.replace(std::path::MAIN_SEPARATOR, "/"))
+}
+
+/// Get the id of the latest version in an object's versions directory.
+async fn latest_version(dir: &Path) -> Result<Option<String>> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut latest: Option<String> = None;
+    while let Some(entry) = entries.next_entry().await? {
+        if let Some(name) = entry.file_name().to_str()
+            && is_version_id(name)
+            && latest.as_deref().is_none_or(|l| name > l)
+        {
+            latest = Some(name.to_string());
+        }
+    }
+
+    Ok(latest)
+}
+
+/// Whether a string is a version id generated by this store.
+fn is_version_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_versions_are_kept() {
+        let root = tempfile::tempdir().unwrap();
+        let store = LocalStore::new(root.path());
+
+        let v1 = store.put("user/a.png", Bytes::from("one")).await.unwrap().unwrap();
+        let v2 = store.put("user/a.png", Bytes::from("two")).await.unwrap().unwrap();
+
+        assert_ne!(v1, v2);
+        assert_eq!(store.get("user/a.png", Some(&v1)).await.unwrap(), "one");
+        assert_eq!(store.get("user/a.png", Some(&v2)).await.unwrap(), "two");
+        assert_eq!(store.get("user/a.png", None).await.unwrap(), "two");
+    }
+
+    #[tokio::test]
+    async fn test_list_by_prefix() {
+        let root = tempfile::tempdir().unwrap();
+        let store = LocalStore::new(root.path());
+
+        store.put("user/a.png", Bytes::from("a")).await.unwrap();
+        store.put("user/b.jpg", Bytes::from("b")).await.unwrap();
+        store.put("thumbnails/user/a/256", Bytes::from("t")).await.unwrap();
+
+        let mut keys: Vec<String> = store
+            .list("user/")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|object| object.key)
+            .collect();
+        keys.sort();
+
+        assert_eq!(keys, ["user/a.png", "user/b.jpg"]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_all_versions() {
+        let root = tempfile::tempdir().unwrap();
+        let store = LocalStore::new(root.path());
+
+        let version = store.put("user/a.png", Bytes::from("a")).await.unwrap();
+        store.delete("user/a.png").await.unwrap();
+
+        assert!(store.get("user/a.png", version.as_deref()).await.is_err());
+        assert!(store.list("").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_keys_outside_root() {
+        let root = tempfile::tempdir().unwrap();
+        let store = LocalStore::new(root.path());
+
+        assert!(store.put("../a.png", Bytes::from("a")).await.is_err());
+        assert!(store.put("/a.png", Bytes::from("a")).await.is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use super::{ObjectInfo, ObjectStore, Result, StorageError};
+
+/// A single stored version of an object
+struct Version {
+    id: String,
+    data: Bytes,
+    created_at: DateTime<Utc>,
+}
+
+/// Versioned object store kept entirely in memory, for tests.
+#[derive(Default)]
+pub struct MemoryStore {
+    objects: Mutex<BTreeMap<String, Vec<Version>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ObjectStore for MemoryStore {
+    async fn put(&self, key: &str, data: Bytes) -> Result<Option<String>> {
+        let id = Uuid::now_v7().simple().to_string();
+
+        self.objects
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .push(Version { id: id.clone(), data, created_at: Utc::now() });
+
+        Ok(Some(id))
+    }
+
+    async fn get(&self, key: &str, version_id: Option<&str>) -> Result<Bytes> {
+        let objects = self.objects.lock().unwrap();
+        let versions = objects
+            .get(key)
+            .ok_or_else(|| StorageError::new(format!("No such key: {}", key)))?;
+
+        let version = match version_id {
+            Some(id) => versions.iter().find(|v| v.id == id),
+            None => versions.last(),
+        };
+
+        version
+            .map(|v| v.data.clone())
+            .ok_or_else(|| StorageError::new(format!("No such version of key: {}", key)))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        let objects = self
+            .objects
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .filter_map(|(key, versions)| {
+                Some(ObjectInfo {
+                    key: key.clone(),
+                    last_modified: Some(versions.last()?.created_at),
+                })
+            })
+            .collect();
+
+        Ok(objects)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.objects.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use async_trait::async_trait;
+use aws_sdk_s3::Client;
+use bytes::Bytes;
+use chrono::DateTime;
+
+use super::{ObjectInfo, ObjectStore, Result, StorageError};
+
+/// Object store backed by a versioned AWS S3 bucket.
+#[derive(Clone)]
+pub struct S3Store {
+    client: Client,
+}
+
+impl S3Store {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl ObjectStore for S3Store {
+    async fn put(&self, key: &str, data: Bytes) -> Result<Option<String>> {
+        let output = s3::upload_object(&self.client, data, key).await?;
+        Ok(output.version_id().map(String::from))
+    }
+
+    async fn get(&self, key: &str, version_id: Option<&str>) -> Result<Bytes> {
+        let data = s3::get_object(&self.client, key, version_id)
+            .await?
+            .body
+            .collect()
+            .await
+            .map_err(|e| StorageError::new(e.to_string()))?
+            .into_bytes();
+
+        Ok(data)
+    }
+
+    /// Only up to 1000 keys are listed at once, so every page is
+    /// fetched.
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        let mut objects = Vec::new();
+        let mut continuation_token = None;
+
+        loop {
+            let output = s3::get_objects(&self.client, prefix, continuation_token.take()).await?;
+
+            objects.extend(output.contents().iter().filter_map(|object| {
+                let key = object.key()?.to_string();
+                let last_modified = object.last_modified().and_then(|time| {
+                    DateTime::from_timestamp(time.secs(), time.subsec_nanos())
+                });
+
+                Some(ObjectInfo { key, last_modified })
+            }));
+
+            if !output.is_truncated().unwrap_or(false) {
+                break;
+            }
+
+            continuation_token = output.next_continuation_token().map(String::from);
+        }
+
+        Ok(objects)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        s3::delete_object(&self.client, key).await?;
+        Ok(())
+    }
+
+    /// Deleting an object from a versioned bucket only hides it
+    /// behind a delete marker, so every version and delete marker
+    /// is deleted instead.
+    async fn delete_all(&self, prefix: &str) -> Result<()> {
+        let mut key_marker = None;
+        let mut version_id_marker = None;
+
+        loop {
+            let output = s3::get_object_versions(
+                &self.client,
+                prefix,
+                key_marker.take(),
+                version_id_marker.take(),
+            )
+            .await?;
+
+            let versions = output
+                .versions()
+                .iter()
+                .filter_map(|v| Some((v.key()?.to_string(), v.version_id()?.to_string())));
+
+            let delete_markers = output
+                .delete_markers()
+                .iter()
+                .filter_map(|m| Some((m.key()?.to_string(), m.version_id()?.to_string())));
+
+            let all_versions: Vec<(String, String)> = versions.chain(delete_markers).collect();
+            for batch in all_versions.chunks(1000) {
+                s3::delete_object_versions(&self.client, batch).await?;
+            }
+
+            if !output.is_truncated().unwrap_or(false) {
+                break;
+            }
+
+            key_marker = output.next_key_marker().map(String::from);
+            version_id_marker = output.next_version_id_marker().map(String::from);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use bytes::Bytes;
+use image::{
+    codecs::{
+        avif::AvifEncoder,
+        jpeg::JpegEncoder,
+        png::{CompressionType, FilterType, PngEncoder},
+    },
+    DynamicImage, ImageFormat, ImageReader,
+};
+use std::io::Cursor;
+use tracing::error;
+
+use errors::ImageError;
+use models::{ContentType, ConvertOptions};
+
+use super::Result;
+
+/// AVIF encoder speed, from 1 (slowest) to 10 (fastest)
+const AVIF_SPEED: u8 = 8;
+
+/// AVIF quality used when none is given
+const AVIF_QUALITY: u8 = 80;
+
+/// Encoder settings; formats they don't apply to ignore them
+#[derive(Clone, Debug, Default)]
+pub struct EncodeOptions {
+    /// JPEG or AVIF quality, from 1 to 100
+    pub quality: Option<u8>,
+
+    /// PNG compression level, from 0 (none) to 9 (best)
+    pub compression: Option<u8>,
+}
+
+impl From<&ConvertOptions> for EncodeOptions {
+    fn from(options: &ConvertOptions) -> Self {
+        Self {
+            quality: options.quality,
+            compression: options.compression,
+        }
+    }
+}
+
+/// Decode image bytes, guessing the format from their contents.
+pub fn decode(data: &[u8]) -> Result<DynamicImage> {
+    ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|_| ImageError::ReadFailure)?
+        .decode()
+        .map_err(|e| {
+            error!("Image decode failed: {}", e);
+            ImageError::ReadFailure
+        })
+}
+
+/// Encode an image in the format matching the given content type.
+pub fn encode(image: &DynamicImage, content_type: &ContentType) -> Result<Bytes> {
+    encode_with(image, content_type, &EncodeOptions::default())
+}
+
+/// Encode an image in the format matching the given content type
+/// using the given encoder settings.
+pub fn encode_with(
+    image: &DynamicImage,
+    content_type: &ContentType,
+    options: &EncodeOptions,
+) -> Result<Bytes> {
+    let format = image_format(content_type)
+        .ok_or(ImageError::InvalidFileType)?;
+
+    if let Some(quality) = options.quality
+        && !(1..=100).contains(&quality)
+    {
+        return Err(ImageError::InvalidTransform(
+            "quality must be between 1 and 100".to_string(),
+        ));
+    }
+
+    if let Some(compression) = options.compression
+        && compression > 9
+    {
+        return Err(ImageError::InvalidTransform(
+            "compression must be between 0 and 9".to_string(),
+        ));
+    }
+
+    // Normalize the pixel layout to one every supported encoder accepts;
+    // JPEG in particular can't store an alpha channel
+    let image = if format == ImageFormat::Jpeg || !image.color().has_alpha() {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+    } else {
+        DynamicImage::ImageRgba8(image.to_rgba8())
+    };
+
+    let mut buffer = Cursor::new(Vec::new());
+    let result = match (format, options.quality, options.compression) {
+        (ImageFormat::Jpeg, Some(quality), _) => image.write_with_encoder(
+            JpegEncoder::new_with_quality(&mut buffer, quality),
+        ),
+        (ImageFormat::Avif, quality, _) => image.write_with_encoder(
+            AvifEncoder::new_with_speed_quality(
+                &mut buffer,
+                AVIF_SPEED,
+                quality.unwrap_or(AVIF_QUALITY),
+            ),
+        ),
+        (ImageFormat::Png, _, Some(compression)) => image.write_with_encoder(
+            PngEncoder::new_with_quality(
+                &mut buffer,
+                match compression {
+                    0 => CompressionType::Uncompressed,
+                    level => CompressionType::Level(level),
+                },
+                FilterType::Adaptive,
+            ),
+        ),
+        _ => image.write_to(&mut buffer, format),
+    };
+
+    result.map_err(|e| {
+        error!("Image encode failed: {}", e);
+        ImageError::ProcessingFailure
+    })?;
+
+    Ok(Bytes::from(buffer.into_inner()))
+}
+
+/// Guess the content type of encoded image data.
+pub fn sniff_content_type(data: &[u8]) -> ContentType {
+    match image::guess_format(data) {
+        Ok(ImageFormat::Jpeg) => ContentType::JPEG,
+        Ok(ImageFormat::Png) => ContentType::PNG,
+        Ok(ImageFormat::Gif) => ContentType::GIF,
+        Ok(ImageFormat::WebP) => ContentType::WEBP,
+        Ok(ImageFormat::Bmp) => ContentType::BMP,
+        Ok(ImageFormat::Avif) => ContentType::AVIF,
+        _ => ContentType::UNKNOWN,
+    }
+}
+
+/// Get the encoder format for a content type.
+fn image_format(content_type: &ContentType) -> Option<ImageFormat> {
+    match content_type {
+        ContentType::JPEG => Some(ImageFormat::Jpeg),
+        ContentType::PNG => Some(ImageFormat::Png),
+        ContentType::GIF => Some(ImageFormat::Gif),
+        ContentType::WEBP => Some(ImageFormat::WebP),
+        ContentType::BMP => Some(ImageFormat::Bmp),
+        ContentType::AVIF => Some(ImageFormat::Avif),
+        ContentType::UNKNOWN => None,
+    }
+}
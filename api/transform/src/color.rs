@@ -0,0 +1,108 @@
+use image::DynamicImage;
+
+use errors::ImageError;
+
+use super::Result;
+
+/// Brighten (positive) or darken (negative) an image by
+/// adding `value` to each color channel.
+pub fn brightness(image: &DynamicImage, value: i32) -> Result<DynamicImage> {
+    if !(-255..=255).contains(&value) {
+        return Err(ImageError::InvalidTransform(
+            "brightness must be between -255 and 255".to_string(),
+        ));
+    }
+
+    Ok(image.brighten(value))
+}
+
+/// Increase (positive) or decrease (negative) an image's contrast
+/// by the given percentage.
+pub fn contrast(image: &DynamicImage, value: f32) -> Result<DynamicImage> {
+    if !(-100.0..=100.0).contains(&value) {
+        return Err(ImageError::InvalidTransform(
+            "contrast must be between -100 and 100".to_string(),
+        ));
+    }
+
+    Ok(image.adjust_contrast(value))
+}
+
+/// Rotate the hue of every pixel in an image by the given
+/// number of degrees.
+pub fn hue_rotate(image: &DynamicImage, degrees: i32) -> Result<DynamicImage> {
+    if !(-360..=360).contains(&degrees) {
+        return Err(ImageError::InvalidTransform(
+            "hue rotation must be between -360 and 360 degrees".to_string(),
+        ));
+    }
+
+    Ok(image.huerotate(degrees))
+}
+
+/// Increase (positive) or decrease (negative) an image's saturation
+/// by the given percentage; -100 removes all color.
+pub fn saturation(image: &DynamicImage, value: f32) -> Result<DynamicImage> {
+    if !(-100.0..=100.0).contains(&value) {
+        return Err(ImageError::InvalidTransform(
+            "saturation must be between -100 and 100".to_string(),
+        ));
+    }
+
+    let factor = 1.0 + value / 100.0;
+    let mut rgba = image.to_rgba8();
+
+    // Move each channel toward or away from the pixel's luminance
+    for pixel in rgba.pixels_mut() {
+        let [r, g, b, _] = pixel.0.map(f32::from);
+        let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+
+        for channel in pixel.0.iter_mut().take(3) {
+            let adjusted = luma + (f32::from(*channel) - luma) * factor;
+            *channel = adjusted.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    Ok(DynamicImage::ImageRgba8(rgba))
+}
+
+/// Convert an image to grayscale, keeping any alpha channel.
+pub fn grayscale(image: &DynamicImage) -> DynamicImage {
+    image.grayscale()
+}
+
+/// Invert the colors of an image, leaving any alpha channel as is.
+pub fn invert(image: &DynamicImage) -> DynamicImage {
+    let mut inverted = image.clone();
+    inverted.invert();
+    inverted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    fn solid(color: [u8; 4]) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba(color)))
+    }
+
+    #[test]
+    fn test_saturation_full_desaturation_is_gray() {
+        let image = saturation(&solid([200, 40, 40, 255]), -100.0).unwrap();
+        let Rgba([r, g, b, a]) = image.get_pixel(0, 0);
+        assert!(r == g && g == b);
+        assert_eq!(a, 255);
+    }
+
+    #[test]
+    fn test_saturation_rejects_out_of_range() {
+        assert!(saturation(&solid([0, 0, 0, 255]), 150.0).is_err());
+    }
+
+    #[test]
+    fn test_invert_keeps_alpha() {
+        let image = invert(&solid([10, 20, 30, 128]));
+        assert_eq!(image.get_pixel(0, 0), Rgba([245, 235, 225, 128]));
+    }
+}
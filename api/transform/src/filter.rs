@@ -0,0 +1,141 @@
+use image::{DynamicImage, Luma, RgbImage};
+use imageproc::{filter::Kernel, gradients::sobel_gradients, map::map_pixels};
+
+use errors::ImageError;
+use models::{BlurOptions, KernelOptions, UnsharpenOptions};
+
+use super::Result;
+
+/// Largest blur sigma accepted, bounding the kernel size
+const MAX_SIGMA: f32 = 100.0;
+
+/// Apply a Gaussian blur to an image.
+pub fn blur(image: &DynamicImage, options: &BlurOptions) -> Result<DynamicImage> {
+    validate_sigma(options.sigma)?;
+    Ok(image.blur(options.sigma))
+}
+
+/// Sharpen an image with an unsharp mask.
+pub fn unsharpen(image: &DynamicImage, options: &UnsharpenOptions) -> Result<DynamicImage> {
+    validate_sigma(options.sigma)?;
+
+    if !(0..=255).contains(&options.threshold) {
+        return Err(ImageError::InvalidFilter(
+            "unsharpen threshold must be between 0 and 255".to_string(),
+        ));
+    }
+
+    Ok(image.unsharpen(options.sigma, options.threshold))
+}
+
+/// Highlight the edges of an image using the Sobel operator.
+pub fn edge_detect(image: &DynamicImage) -> DynamicImage {
+    let gradients = sobel_gradients(&image.to_luma8());
+    let edges = map_pixels(&gradients, |_, _, Luma([magnitude])| {
+        Luma([magnitude.min(u8::MAX as u16) as u8])
+    });
+
+    with_alpha_of(image, DynamicImage::ImageLuma8(edges))
+}
+
+/// Convolve an image's color channels with a user-supplied kernel.
+pub fn convolve(image: &DynamicImage, options: &KernelOptions) -> Result<DynamicImage> {
+    let size = match options.kernel.len() {
+        9 => 3,
+        25 => 5,
+        len => {
+            return Err(ImageError::InvalidFilter(
+                format!("kernel must have 9 (3x3) or 25 (5x5) values, not {}", len),
+            ));
+        }
+    };
+
+    if options.kernel.iter().any(|weight| !weight.is_finite()) {
+        return Err(ImageError::InvalidFilter(
+            "kernel values must be finite numbers".to_string(),
+        ));
+    }
+
+    let divisor = options.divisor.unwrap_or_else(|| {
+        let sum: f32 = options.kernel.iter().sum();
+        if sum == 0.0 { 1.0 } else { sum }
+    });
+
+    if divisor == 0.0 || !divisor.is_finite() {
+        return Err(ImageError::InvalidFilter(
+            "kernel divisor must be a non-zero finite number".to_string(),
+        ));
+    }
+
+    let weights: Vec<f32> = options.kernel
+        .iter()
+        .map(|weight| weight / divisor)
+        .collect();
+
+    let filtered: RgbImage = Kernel::new(&weights, size, size)
+        .filter(&image.to_rgb8(), |channel, value| {
+            *channel = value.round().clamp(0.0, 255.0) as u8;
+        });
+
+    Ok(with_alpha_of(image, DynamicImage::ImageRgb8(filtered)))
+}
+
+fn validate_sigma(sigma: f32) -> Result<()> {
+    if !(sigma > 0.0 && sigma <= MAX_SIGMA) {
+        return Err(ImageError::InvalidFilter(
+            format!("sigma must be greater than 0 and at most {}", MAX_SIGMA),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Copy the source image's alpha channel, if it has one, onto a
+/// filtered image so that filters only affect color.
+fn with_alpha_of(source: &DynamicImage, filtered: DynamicImage) -> DynamicImage {
+    if !source.color().has_alpha() {
+        return filtered;
+    }
+
+    let mut rgba = filtered.to_rgba8();
+    for (pixel, source_pixel) in rgba.pixels_mut().zip(source.to_rgba8().pixels()) {
+        pixel.0[3] = source_pixel.0[3];
+    }
+
+    DynamicImage::ImageRgba8(rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kernel(values: Vec<f32>) -> KernelOptions {
+        KernelOptions { kernel: values, divisor: None }
+    }
+
+    #[test]
+    fn test_convolve_rejects_wrong_kernel_size() {
+        let image = DynamicImage::new_rgb8(4, 4);
+        let result = convolve(&image, &kernel(vec![1.0; 4]));
+        assert!(matches!(result, Err(ImageError::InvalidFilter(_))));
+    }
+
+    #[test]
+    fn test_convolve_identity_kernel() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(3, 3, |x, y| {
+            image::Rgb([(x * 50) as u8, (y * 50) as u8, 7])
+        }));
+        let mut identity = vec![0.0; 9];
+        identity[4] = 1.0;
+
+        let result = convolve(&image, &kernel(identity)).unwrap();
+        assert_eq!(result.to_rgb8(), image.to_rgb8());
+    }
+
+    #[test]
+    fn test_blur_rejects_non_positive_sigma() {
+        let image = DynamicImage::new_rgb8(4, 4);
+        let result = blur(&image, &BlurOptions { sigma: 0.0 });
+        assert!(matches!(result, Err(ImageError::InvalidFilter(_))));
+    }
+}
@@ -0,0 +1,151 @@
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+use imageproc::geometric_transformations::{rotate_about_center, Interpolation};
+
+use errors::ImageError;
+use models::{CropOptions, FlipDirection, RotateOptions};
+
+use super::Result;
+
+/// Crop an image to the given rectangle.
+pub fn crop(image: &DynamicImage, options: &CropOptions) -> Result<DynamicImage> {
+    if options.width == 0 || options.height == 0 {
+        return Err(ImageError::InvalidTransform(
+            "crop width and height must be greater than zero".to_string(),
+        ));
+    }
+
+    let right = options.x.checked_add(options.width);
+    let bottom = options.y.checked_add(options.height);
+
+    if !matches!(right, Some(r) if r <= image.width())
+        || !matches!(bottom, Some(b) if b <= image.height())
+    {
+        return Err(ImageError::InvalidTransform(
+            "crop rectangle exceeds image bounds".to_string(),
+        ));
+    }
+
+    Ok(image.crop_imm(options.x, options.y, options.width, options.height))
+}
+
+/// Rotate an image clockwise. Right-angle rotations are lossless;
+/// any other angle expands the canvas to fit the rotated image and
+/// fills the exposed corners with the background color.
+pub fn rotate(image: &DynamicImage, options: &RotateOptions) -> Result<DynamicImage> {
+    if !options.angle.is_finite() {
+        return Err(ImageError::InvalidTransform(
+            "rotation angle must be a finite number".to_string(),
+        ));
+    }
+
+    let angle = options.angle.rem_euclid(360.0);
+
+    let rotated = match angle {
+        0.0 => image.clone(),
+        90.0 => image.rotate90(),
+        180.0 => image.rotate180(),
+        270.0 => image.rotate270(),
+        _ => rotate_arbitrary(image, angle, Rgba(options.background.0)),
+    };
+
+    Ok(rotated)
+}
+
+/// Mirror an image along the given axis.
+pub fn flip(image: &DynamicImage, direction: FlipDirection) -> DynamicImage {
+    match direction {
+        FlipDirection::Horizontal => image.fliph(),
+        FlipDirection::Vertical => image.flipv(),
+    }
+}
+
+fn rotate_arbitrary(image: &DynamicImage, angle: f32, background: Rgba<u8>) -> DynamicImage {
+    let theta = angle.to_radians();
+    let (sin, cos) = theta.sin_cos();
+    let (width, height) = (image.width() as f32, image.height() as f32);
+
+    // Bounding box of the rotated image
+    let canvas_width = (width * cos.abs() + height * sin.abs()).round() as u32;
+    let canvas_height = (width * sin.abs() + height * cos.abs()).round() as u32;
+
+    // Rotate on a square canvas as wide as the source's diagonal,
+    // which holds both the source and the rotated result, so that
+    // nothing is clipped before or during the rotation
+    let side = width.hypot(height).ceil() as u32;
+    let side = side.max(canvas_width).max(canvas_height);
+
+    let mut canvas = RgbaImage::from_pixel(side, side, background);
+    imageops::replace(
+        &mut canvas,
+        &image.to_rgba8(),
+        (side as i64 - image.width() as i64) / 2,
+        (side as i64 - image.height() as i64) / 2,
+    );
+
+    let rotated = rotate_about_center(
+        &canvas,
+        theta,
+        Interpolation::Bilinear,
+        background,
+    );
+
+    // Trim the canvas to the bounding box
+    let rotated = imageops::crop_imm(
+        &rotated,
+        (side - canvas_width) / 2,
+        (side - canvas_height) / 2,
+        canvas_width,
+        canvas_height,
+    )
+    .to_image();
+
+    DynamicImage::ImageRgba8(rotated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::Color;
+
+    fn rotate_options(angle: f32) -> RotateOptions {
+        RotateOptions { angle, background: Color::default() }
+    }
+
+    #[test]
+    fn test_crop_out_of_bounds() {
+        let image = DynamicImage::new_rgb8(100, 50);
+        let options = CropOptions { x: 60, y: 0, width: 50, height: 50 };
+        assert!(crop(&image, &options).is_err());
+    }
+
+    #[test]
+    fn test_rotate_right_angle_swaps_dimensions() {
+        let image = DynamicImage::new_rgb8(100, 50);
+        let rotated = rotate(&image, &rotate_options(-90.0)).unwrap();
+        assert_eq!((rotated.width(), rotated.height()), (50, 100));
+    }
+
+    #[test]
+    fn test_rotate_arbitrary_expands_canvas() {
+        let image = DynamicImage::new_rgb8(100, 100);
+        let rotated = rotate(&image, &rotate_options(45.0)).unwrap();
+        assert_eq!((rotated.width(), rotated.height()), (141, 141));
+    }
+
+    #[test]
+    fn test_rotate_arbitrary_keeps_content_of_non_square_image() {
+        let white = image::Rgb([255, 255, 255]);
+        let image = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(200, 50, white));
+        let rotated = rotate(&image, &rotate_options(89.0)).unwrap();
+        assert_eq!((rotated.width(), rotated.height()), (53, 201));
+
+        // Roughly all of the source's 10,000 pixels remain, give or
+        // take interpolation at the edges
+        let kept = rotated
+            .to_rgba8()
+            .pixels()
+            .filter(|pixel| pixel.0[0] > 127 && pixel.0[3] > 127)
+            .count();
+        assert!((9500..=10500).contains(&kept), "kept {} pixels", kept);
+    }
+}
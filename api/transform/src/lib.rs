@@ -0,0 +1,144 @@
+//! Image Transformations
+
+use bytes::Bytes;
+use image::DynamicImage;
+
+use errors::ImageError;
+use models::{ContentType, ConvertOptions, Operation};
+
+mod codec;
+mod color;
+mod filter;
+mod geometry;
+mod resize;
+mod thumbnail;
+
+pub use codec::{decode, encode, encode_with, sniff_content_type, EncodeOptions};
+pub use color::{brightness, contrast, grayscale, hue_rotate, invert, saturation};
+pub use filter::{blur, convolve, edge_detect, unsharpen};
+pub use geometry::{crop, flip, rotate};
+pub use resize::resize;
+pub use thumbnail::{thumbnail, thumbnails, THUMBNAIL_SIZES};
+
+type Result<T> = anyhow::Result<T, ImageError>;
+
+/// Most operations accepted in a single pipeline
+pub const MAX_OPERATIONS: usize = 20;
+
+/// Encoded image data produced by a transformation
+pub struct Output {
+    pub data: Bytes,
+    pub dimensions: (u32, u32),
+    pub content_type: ContentType,
+}
+
+/// Decode an image, apply a sequence of operations to it in order,
+/// and re-encode it in its original format, or in the format given
+/// by the last `convert` operation if there is one.
+pub fn apply(
+    data: &[u8],
+    content_type: &ContentType,
+    operations: &[Operation],
+) -> Result<Output> {
+    let transformed = render(data, operations)?;
+    to_output(&transformed, content_type, operations)
+}
+
+/// Like [`apply`], but optionally shrinks the result so that its
+/// longest edge is at most `max_size` pixels.
+pub fn preview(
+    data: &[u8],
+    content_type: &ContentType,
+    operations: &[Operation],
+    max_size: Option<u32>,
+) -> Result<Output> {
+    let mut transformed = render(data, operations)?;
+
+    if let Some(max_size) = max_size {
+        if max_size == 0 {
+            return Err(ImageError::InvalidTransform(
+                "preview size must be greater than zero".to_string(),
+            ));
+        }
+
+        if transformed.width() > max_size || transformed.height() > max_size {
+            transformed = transformed.thumbnail(max_size, max_size);
+        }
+    }
+
+    to_output(&transformed, content_type, operations)
+}
+
+/// Apply a single operation to a decoded image.
+pub fn apply_operation(
+    image: &DynamicImage,
+    operation: &Operation,
+) -> Result<DynamicImage> {
+    match operation {
+        Operation::Resize(options) => resize(image, options),
+        Operation::Crop(options) => crop(image, options),
+        Operation::Rotate(options) => rotate(image, options),
+        Operation::Flip(direction) => Ok(flip(image, *direction)),
+        Operation::Blur(options) => blur(image, options),
+        Operation::Unsharpen(options) => unsharpen(image, options),
+        Operation::EdgeDetect => Ok(edge_detect(image)),
+        Operation::Convolve(options) => convolve(image, options),
+        Operation::Brightness(value) => brightness(image, *value),
+        Operation::Contrast(value) => contrast(image, *value),
+        Operation::HueRotate(degrees) => hue_rotate(image, *degrees),
+        Operation::Saturation(value) => saturation(image, *value),
+        Operation::Grayscale => Ok(grayscale(image)),
+        Operation::Invert => Ok(invert(image)),
+        // Conversion happens when the result is encoded
+        Operation::Convert(_) => Ok(image.clone()),
+    }
+}
+
+/// Decode an image and apply a sequence of operations to it in order.
+fn render(data: &[u8], operations: &[Operation]) -> Result<DynamicImage> {
+    if operations.is_empty() {
+        return Err(ImageError::InvalidTransform(
+            "at least one operation is required".to_string(),
+        ));
+    }
+
+    if operations.len() > MAX_OPERATIONS {
+        return Err(ImageError::InvalidTransform(
+            format!("no more than {} operations are allowed", MAX_OPERATIONS),
+        ));
+    }
+
+    let mut transformed = decode(data)?;
+    for operation in operations {
+        if !matches!(operation, Operation::Convert(_)) {
+            transformed = apply_operation(&transformed, operation)?;
+        }
+    }
+
+    Ok(transformed)
+}
+
+fn to_output(
+    image: &DynamicImage,
+    content_type: &ContentType,
+    operations: &[Operation],
+) -> Result<Output> {
+    let convert: Option<&ConvertOptions> = operations
+        .iter()
+        .rev()
+        .find_map(|operation| match operation {
+            Operation::Convert(options) => Some(options),
+            _ => None,
+        });
+
+    let (content_type, options) = match convert {
+        Some(options) => (options.format.into(), EncodeOptions::from(options)),
+        None => (content_type.clone(), EncodeOptions::default()),
+    };
+
+    Ok(Output {
+        data: encode_with(image, &content_type, &options)?,
+        dimensions: (image.width(), image.height()),
+        content_type,
+    })
+}
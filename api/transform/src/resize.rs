@@ -0,0 +1,120 @@
+use image::{imageops::FilterType, DynamicImage};
+
+use errors::ImageError;
+use models::{ResizeFilter, ResizeFit, ResizeOptions};
+
+use super::Result;
+
+/// Largest width or height an image may be resized to
+const MAX_DIMENSION: u32 = 10_000;
+
+/// Resize an image according to the given options.
+pub fn resize(image: &DynamicImage, options: &ResizeOptions) -> Result<DynamicImage> {
+    let (width, height) = target_dimensions(
+        (image.width(), image.height()),
+        options,
+    )?;
+    let filter = filter_type(options.filter);
+
+    let resized = match options.fit {
+        ResizeFit::Contain => image.resize(width, height, filter),
+        ResizeFit::Cover => image.resize_to_fill(width, height, filter),
+        ResizeFit::Exact => image.resize_exact(width, height, filter),
+    };
+
+    Ok(resized)
+}
+
+/// Work out the target dimensions, deriving a missing width or
+/// height from the source image's aspect ratio.
+fn target_dimensions(
+    source: (u32, u32),
+    options: &ResizeOptions,
+) -> Result<(u32, u32)> {
+    let (width, height) = match (options.width, options.height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => (width, scale(source.1, width, source.0)),
+        (None, Some(height)) => (scale(source.0, height, source.1), height),
+        (None, None) => {
+            return Err(ImageError::InvalidTransform(
+                "width or height is required".to_string(),
+            ));
+        }
+    };
+
+    if width == 0 || height == 0 {
+        return Err(ImageError::InvalidTransform(
+            "width and height must be greater than zero".to_string(),
+        ));
+    }
+
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(ImageError::InvalidTransform(
+            format!("width and height must not exceed {}", MAX_DIMENSION),
+        ));
+    }
+
+    Ok((width, height))
+}
+
+/// Scale `value` by the ratio `numerator / denominator`.
+fn scale(value: u32, numerator: u32, denominator: u32) -> u32 {
+    let scaled = value as f64 * numerator as f64 / denominator.max(1) as f64;
+    (scaled.round() as u32).max(1)
+}
+
+fn filter_type(filter: ResizeFilter) -> FilterType {
+    match filter {
+        ResizeFilter::Nearest => FilterType::Nearest,
+        ResizeFilter::Triangle => FilterType::Triangle,
+        ResizeFilter::CatmullRom => FilterType::CatmullRom,
+        ResizeFilter::Gaussian => FilterType::Gaussian,
+        ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(width: Option<u32>, height: Option<u32>) -> ResizeOptions {
+        ResizeOptions {
+            width,
+            height,
+            fit: ResizeFit::default(),
+            filter: ResizeFilter::default(),
+        }
+    }
+
+    #[test]
+    fn test_target_dimensions_derives_missing_height() {
+        let dimensions = target_dimensions((400, 300), &options(Some(200), None));
+        assert_eq!(dimensions.unwrap(), (200, 150));
+    }
+
+    #[test]
+    fn test_target_dimensions_derives_missing_width() {
+        let dimensions = target_dimensions((400, 300), &options(None, Some(150)));
+        assert_eq!(dimensions.unwrap(), (200, 150));
+    }
+
+    #[test]
+    fn test_target_dimensions_rejects_missing_width_and_height() {
+        assert!(target_dimensions((400, 300), &options(None, None)).is_err());
+    }
+
+    #[test]
+    fn test_target_dimensions_rejects_zero() {
+        assert!(target_dimensions((400, 300), &options(Some(0), Some(10))).is_err());
+    }
+
+    #[test]
+    fn test_resize_cover_fills_target() {
+        let image = DynamicImage::new_rgb8(400, 300);
+        let mut opts = options(Some(100), Some(100));
+        opts.fit = ResizeFit::Cover;
+
+        let resized = resize(&image, &opts).unwrap();
+        assert_eq!((resized.width(), resized.height()), (100, 100));
+    }
+}
@@ -0,0 +1,49 @@
+use image::DynamicImage;
+
+use models::ContentType;
+
+use super::{decode, encode_with, EncodeOptions, Output, Result};
+
+/// Longest-edge sizes, in pixels, of the thumbnails kept for each
+/// image version
+pub const THUMBNAIL_SIZES: [u32; 2] = [256, 1024];
+
+/// JPEG quality used for thumbnails of opaque images
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// Decode an image and render a thumbnail of it at each of the
+/// [`THUMBNAIL_SIZES`].
+pub fn thumbnails(data: &[u8]) -> Result<Vec<(u32, Output)>> {
+    let image = decode(data)?;
+
+    THUMBNAIL_SIZES
+        .iter()
+        .map(|&size| Ok((size, thumbnail(&image, size)?)))
+        .collect()
+}
+
+/// Shrink an image so that its longest edge is at most `size` pixels.
+/// Opaque images are encoded as JPEG and the rest as PNG.
+pub fn thumbnail(image: &DynamicImage, size: u32) -> Result<Output> {
+    let thumbnail = if image.width() > size || image.height() > size {
+        image.thumbnail(size, size)
+    } else {
+        image.clone()
+    };
+
+    let (content_type, options) = if thumbnail.color().has_alpha() {
+        (ContentType::PNG, EncodeOptions::default())
+    } else {
+        let options = EncodeOptions {
+            quality: Some(THUMBNAIL_QUALITY),
+            ..Default::default()
+        };
+        (ContentType::JPEG, options)
+    };
+
+    Ok(Output {
+        data: encode_with(&thumbnail, &content_type, &options)?,
+        dimensions: (thumbnail.width(), thumbnail.height()),
+        content_type,
+    })
+}
@@ -64,6 +64,23 @@ export const getImageDataUrl = async (imageId: string): object | null => {
     return null;
 }
 
+export const getImageThumbnailUrl = async (
+    imageId: string,
+    size: number = 256,
+): string | null => {
+    try {
+      const response = await fetch(`${imageUrl(imageId)}/thumbnail?size=${size}`);
+      if (response.ok) {
+        const blob = await response.blob();
+        return URL.createObjectURL(blob);
+      }
+    } catch (err) {
+      console.error(`Failed to fetch image thumbnail:`, err);
+    }
+
+    return null;
+}
+
 export const getImageMetadata = async (imageId: string): ImageMeta | null => {
     try {
       const response = await fetch(`${imageUrl(imageId)}/meta`);
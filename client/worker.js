@@ -105,15 +105,10 @@ async function buildNewRequest(request, headers, body = null) {
 
     if (body != null) {
         newBody = body;
-    } else {
-        try {
-            // Try to parse the old request's body as JSON
-            const responseJson = await request.json();
-            newBody = JSON.stringify(responseJson);
-        } catch (error) {
-            // That didn't work, so use the ReadableStream
-            newBody = request.body;
-        }
+    } else if (request.method !== "GET" && request.method !== "HEAD") {
+        // Buffer the old request's body (JSON or multipart form
+        // data), since a stream can't be sent with a new request
+        newBody = await request.blob();
     }
 
     return new Request(request.url, {
@@ -162,17 +157,12 @@ async function updateRequest(request, route, tokens) {
 async function interceptRequest(request) {
     const url = new URL(request.url);
     const route = getRoutePath(url.pathname);
-    const isUploadRequest = route === "/images" && request.method === "POST";
 
     let tokens = await storage.get("tokens");
 
     const isProtectedUrl =
         !!tokens &&
-        protectedUrls.some((path) => route.startsWith(path)) &&
-        // We handle user authentication differently for image upload
-        // requests, so we won't consider `/images` a protected URL
-        // if the request message is POST
-        !isUploadRequest;
+        protectedUrls.some((path) => route.startsWith(path));
 
     if (isProtectedUrl) {
         let newRequest;
@@ -230,7 +220,6 @@ async function interceptRequest(request) {
 
     // Just return the original request if we got this far,
     // since that means one of the following is true:
-    // * It's an image upload request
     // * We're missing tokens
     // * The URL is neither protected nor for authorization
     return fetch(request);